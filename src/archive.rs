@@ -0,0 +1,261 @@
+use anyhow::{Context, Result};
+use flate2::read::GzDecoder;
+use std::env;
+use std::fs::{self, File};
+use std::io::{copy, Read};
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Component, Path, PathBuf};
+
+/// Packaging of a release asset.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    TarGz,
+    /// The asset is the executable itself
+    Binary,
+}
+
+impl ArchiveFormat {
+    /// Parse a format name as used in `TFENV_ARCHIVE_FORMAT`.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "zip" => Some(Self::Zip),
+            "tar.gz" | "tgz" | "targz" => Some(Self::TarGz),
+            "binary" | "raw" | "none" => Some(Self::Binary),
+            _ => None,
+        }
+    }
+
+    /// `TFENV_ARCHIVE_FORMAT`, set for mirrors that repackage releases.
+    pub fn from_env() -> Option<Self> {
+        env::var("TFENV_ARCHIVE_FORMAT")
+            .ok()
+            .and_then(|f| Self::parse(&f))
+    }
+
+    /// The format a file name's extension names (`.zip`, `.tar.gz`, `.tgz`), if any.
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        if name.ends_with(".zip") {
+            Some(Self::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else {
+            None
+        }
+    }
+
+    /// Suffix appended to release asset names.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Zip => ".zip",
+            Self::TarGz => ".tar.gz",
+            Self::Binary => "",
+        }
+    }
+
+    /// Detect the format from the file's leading bytes, for archives whose format
+    /// is not otherwise known (e.g. local files without an archive extension).
+    pub fn sniff(path: &Path) -> Result<Self> {
+        let mut magic = [0u8; 4];
+        let mut f = File::open(path)
+            .with_context(|| format!("failed to open {} for extraction", path.display()))?;
+        let n = f.read(&mut magic)?;
+        Ok(match &magic[..n] {
+            [b'P', b'K', 3, 4] => Self::Zip,
+            [0x1f, 0x8b, ..] => Self::TarGz,
+            _ => Self::Binary,
+        })
+    }
+}
+
+/// Upper bound on the bytes written while extracting one archive. Releases are a
+/// few hundred MB at most; anything larger is treated as a decompression bomb.
+const MAX_EXTRACTED_BYTES: u64 = 2 * 1024 * 1024 * 1024;
+
+/// Upper bound on the number of entries extracted from one archive.
+const MAX_ENTRIES: usize = 10_000;
+
+/// Extract every member of `archive`, packaged as `format`, into `out_dir` and
+/// make sure the product binary ends up at `out_dir/<binary_name>`.
+///
+/// Absolute paths, `..` components, links and archives exceeding the size limits
+/// are rejected. Returns the extracted files as `/`-separated relative paths.
+pub fn extract_archive(
+    archive: &Path,
+    out_dir: &Path,
+    binary_name: &str,
+    format: ArchiveFormat,
+) -> Result<Vec<String>> {
+    fs::create_dir_all(out_dir)?;
+    let mut budget = ExtractBudget::default();
+    let mut files = match format {
+        ArchiveFormat::Zip => extract_zip(archive, out_dir, &mut budget)?,
+        ArchiveFormat::TarGz => extract_tar_gz(archive, out_dir, &mut budget)?,
+        ArchiveFormat::Binary => {
+            fs::copy(archive, out_dir.join(binary_name))
+                .context("failed to copy binary into place")?;
+            vec![binary_name.to_string()]
+        }
+    };
+
+    // Some archives nest the binary in a directory; tfenv runs it from the top level
+    if !files.iter().any(|f| f == binary_name) {
+        let nested = files
+            .iter()
+            .position(|f| is_binary_entry(f, binary_name))
+            .ok_or_else(|| anyhow::anyhow!("{} binary not found inside archive", binary_name))?;
+        fs::rename(out_dir.join(&files[nested]), out_dir.join(binary_name))
+            .context("failed to move binary to the top of the version directory")?;
+        files[nested] = binary_name.to_string();
+    }
+    set_mode(&out_dir.join(binary_name), 0o755)?;
+    files.sort();
+    Ok(files)
+}
+
+/// Bytes and entries left before extraction is aborted.
+struct ExtractBudget {
+    bytes: u64,
+    entries: usize,
+}
+
+impl Default for ExtractBudget {
+    fn default() -> Self {
+        Self {
+            bytes: MAX_EXTRACTED_BYTES,
+            entries: MAX_ENTRIES,
+        }
+    }
+}
+
+impl ExtractBudget {
+    fn take_entry(&mut self) -> Result<()> {
+        if self.entries == 0 {
+            anyhow::bail!("archive has more than {} entries", MAX_ENTRIES);
+        }
+        self.entries -= 1;
+        Ok(())
+    }
+
+    /// Copy `reader` to a new file at `out_path`, counting the bytes actually
+    /// written rather than trusting sizes declared in archive headers.
+    fn write_file(&mut self, reader: &mut dyn Read, out_path: &Path) -> Result<()> {
+        if let Some(parent) = out_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut outfile = File::create(out_path)
+            .with_context(|| format!("failed to create {}", out_path.display()))?;
+        let written = copy(&mut reader.take(self.bytes + 1), &mut outfile)?;
+        if written > self.bytes {
+            anyhow::bail!(
+                "archive expands to more than {} bytes; refusing to extract",
+                MAX_EXTRACTED_BYTES
+            );
+        }
+        self.bytes -= written;
+        Ok(())
+    }
+}
+
+/// Validate an archive member name and turn it into a path relative to the
+/// output directory. Absolute paths and `..` components are errors.
+fn safe_relative_path(name: &str) -> Result<PathBuf> {
+    let mut out = PathBuf::new();
+    for component in Path::new(name).components() {
+        match component {
+            Component::Normal(part) => out.push(part),
+            Component::CurDir => {}
+            _ => anyhow::bail!("refusing to extract unsafe path '{}'", name),
+        }
+    }
+    if out.as_os_str().is_empty() {
+        anyhow::bail!("refusing to extract empty path '{}'", name);
+    }
+    Ok(out)
+}
+
+fn relative_display(path: &Path) -> String {
+    path.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> Result<()> {
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+        .with_context(|| format!("failed to set permissions on {}", path.display()))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> Result<()> {
+    Ok(())
+}
+
+/// Permissions for an extracted file: executable if any execute bit was set.
+fn file_mode(archived_mode: Option<u32>) -> u32 {
+    match archived_mode {
+        Some(mode) if mode & 0o111 != 0 => 0o755,
+        _ => 0o644,
+    }
+}
+
+/// Whether an archive member is the product binary (`terraform`, `terraform.exe`).
+fn is_binary_entry(name: &str, binary_name: &str) -> bool {
+    let file = name.rsplit('/').next().unwrap_or(name);
+    file == binary_name || Some(file) == binary_name.strip_suffix(".exe")
+}
+
+fn extract_zip(zip_path: &Path, out_dir: &Path, budget: &mut ExtractBudget) -> Result<Vec<String>> {
+    let file = File::open(zip_path).context("failed to open zip file for extraction")?;
+    let mut archive = zip::ZipArchive::new(file).context("failed to read zip archive")?;
+    let mut files = Vec::new();
+    for i in 0..archive.len() {
+        budget.take_entry()?;
+        let mut entry = archive.by_index(i).context("zip entry error")?;
+        let rel = safe_relative_path(entry.name())?;
+        let out_path = out_dir.join(&rel);
+        if entry.is_dir() {
+            fs::create_dir_all(&out_path)?;
+            continue;
+        }
+        if entry
+            .unix_mode()
+            .is_some_and(|mode| mode & 0o170000 == 0o120000)
+        {
+            anyhow::bail!("refusing to extract symlink '{}'", entry.name());
+        }
+        let mode = file_mode(entry.unix_mode());
+        budget.write_file(&mut entry, &out_path)?;
+        set_mode(&out_path, mode)?;
+        files.push(relative_display(&rel));
+    }
+    Ok(files)
+}
+
+fn extract_tar_gz(path: &Path, out_dir: &Path, budget: &mut ExtractBudget) -> Result<Vec<String>> {
+    let file = File::open(path).context("failed to open tar.gz file for extraction")?;
+    let mut archive = tar::Archive::new(GzDecoder::new(file));
+    let mut files = Vec::new();
+    for entry in archive.entries().context("failed to read tar.gz archive")? {
+        budget.take_entry()?;
+        let mut entry = entry.context("tar entry error")?;
+        let name = entry.path()?.to_string_lossy().into_owned();
+        let rel = safe_relative_path(&name)?;
+        let out_path = out_dir.join(&rel);
+        let kind = entry.header().entry_type();
+        if kind.is_dir() {
+            fs::create_dir_all(&out_path)?;
+            continue;
+        }
+        if !kind.is_file() {
+            anyhow::bail!("refusing to extract '{}': unsupported entry type", name);
+        }
+        let mode = file_mode(entry.header().mode().ok());
+        budget.write_file(&mut entry, &out_path)?;
+        set_mode(&out_path, mode)?;
+        files.push(relative_display(&rel));
+    }
+    Ok(files)
+}
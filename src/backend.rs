@@ -0,0 +1,430 @@
+use crate::archive::ArchiveFormat;
+use scraper::{Html, Selector};
+use semver::Version;
+use std::env;
+
+/// How a release's SHA256SUMS file is signed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignatureScheme {
+    /// No signature is published; only the checksums are verified
+    None,
+    /// A detached gpg signature at `<sums url>.sig`, checked when gpg verification is enabled
+    GpgDetached,
+}
+
+/// Everything tfenv needs to know about where and how a product is released.
+///
+/// Terraform (and other releases.hashicorp.com products), OpenTofu and Terragrunt are
+/// built in; tools embedding the library can add their own with
+/// [`register_backend`](crate::product::register_backend).
+pub trait ProductBackend: Send + Sync {
+    /// Name used in `TFENV_PRODUCT`/`--product`, listings and install paths
+    fn name(&self) -> &str;
+
+    /// Executable inside the release archive, without `.exe`
+    fn binary(&self) -> &str {
+        self.name()
+    }
+
+    /// Version file looked up from the working directory upwards and in `$HOME`
+    fn version_file(&self) -> String {
+        format!(".{}-version", self.name())
+    }
+
+    /// Version files in order of preference within one directory; the nearest
+    /// directory holding any of them wins
+    fn version_files(&self) -> Vec<String> {
+        vec![self.version_file()]
+    }
+
+    /// Environment variable overriding the version (`TFENV_PACKER_VERSION`, ...)
+    fn version_env(&self) -> String {
+        format!(
+            "TFENV_{}_VERSION",
+            self.name().to_uppercase().replace('-', "_")
+        )
+    }
+
+    /// Page listing the available releases
+    fn listing_url(&self) -> String;
+
+    /// Versions found in the body of `listing_url`, newest first
+    fn parse_listing(&self, body: &str) -> Vec<Version>;
+
+    /// Format release assets are published in, and extracted as. Defaults to zip,
+    /// or `TFENV_ARCHIVE_FORMAT` for mirrors that repackage releases
+    fn archive_format(&self) -> ArchiveFormat {
+        ArchiveFormat::from_env().unwrap_or(ArchiveFormat::Zip)
+    }
+
+    /// File name of the release asset for a platform (in release naming, e.g. `linux`/`amd64`)
+    fn asset_name(&self, version: &str, os: &str, arch: &str) -> String;
+
+    fn asset_url(&self, version: &str, asset: &str) -> String;
+
+    /// The `<os>_<arch>` an asset listed in SHA256SUMS was built for, if it is a
+    /// release asset of `version`
+    fn asset_platform(&self, version: &str, asset: &str) -> Option<String>;
+
+    /// Name of the SHA256SUMS file published with `version`, if any
+    fn sha256sums_file(&self, _version: &str) -> Option<String> {
+        None
+    }
+
+    /// URL of the SHA256SUMS file for `version`; releases without one are not
+    /// verified unless checksums are pinned
+    fn sha256sums_url(&self, version: &str) -> Option<String> {
+        self.sha256sums_file(version)
+            .map(|file| self.asset_url(version, &file))
+    }
+
+    /// URL of the checksum file covering `asset`, for products that publish one per
+    /// platform or asset rather than per release
+    fn asset_sha256sums_url(&self, version: &str, _asset: &str) -> Option<String> {
+        self.sha256sums_url(version)
+    }
+
+    fn signature_scheme(&self) -> SignatureScheme {
+        SignatureScheme::None
+    }
+
+    /// Arguments making the binary print its version, preferably as JSON
+    fn version_args(&self) -> Vec<String> {
+        vec!["version".to_string()]
+    }
+
+    /// Attribute constraining the product's version in configuration files
+    fn constraint_attribute(&self) -> &str {
+        "required_version"
+    }
+
+    /// Block holding `constraint_attribute` (`terraform { required_version = ... }`),
+    /// or `None` for a top-level attribute
+    fn constraint_block(&self) -> Option<&str> {
+        Some("terraform")
+    }
+
+    /// Whether `file_name` is a configuration file that may hold `constraint_attribute`
+    fn is_constraint_file(&self, _file_name: &str) -> bool {
+        false
+    }
+
+    /// Whether a constraint in the working directory's configuration is used when
+    /// no version file pins the product
+    fn resolves_from_constraint(&self) -> bool {
+        false
+    }
+
+    /// Whether the binary reads and writes Terraform state (`terraform.tfstate`),
+    /// so that the version recorded there applies to it
+    fn reads_terraform_state(&self) -> bool {
+        false
+    }
+}
+
+/// Mirror of the product's releases: `TFENV_<PRODUCT>_REMOTE`, or for Terraform
+/// also `TFENV_REMOTE`. Per product, since one process may resolve several (e.g.
+/// `tfenv terragrunt`).
+fn remote_override(product: &str) -> Option<String> {
+    let var = format!("TFENV_{}_REMOTE", product.to_uppercase().replace('-', "_"));
+    let remote = match env::var(var) {
+        Ok(remote) => remote,
+        Err(_) if product == "terraform" => env::var("TFENV_REMOTE").ok()?,
+        Err(_) => return None,
+    };
+    Some(remote).filter(|r| !r.is_empty())
+}
+
+/// The product's mirror, or `default`, with a trailing slash.
+fn remote_base(product: &str, default: &str) -> String {
+    let remote = remote_override(product).unwrap_or_else(|| default.to_string());
+    if remote.ends_with('/') {
+        remote
+    } else {
+        format!("{}/", remote)
+    }
+}
+
+/// HashiCorp-style asset name, `<prefix>_<version>_<os>_<arch><extension>`, also
+/// used by OpenTofu.
+pub fn release_asset_name(
+    prefix: &str,
+    version: &str,
+    os: &str,
+    arch: &str,
+    format: ArchiveFormat,
+) -> String {
+    format!(
+        "{}_{}_{}_{}{}",
+        prefix,
+        version,
+        os,
+        arch,
+        format.extension()
+    )
+}
+
+/// Versions linked from `body` by hrefs containing `prefix`, newest first.
+fn versions_linked(body: &str, prefix: &str, anchored: bool) -> Vec<Version> {
+    let doc = Html::parse_document(body);
+    let selector = Selector::parse("a").unwrap();
+    let mut versions: Vec<Version> = Vec::new();
+    for el in doc.select(&selector) {
+        if let Some(href) = el.value().attr("href") {
+            let v = if anchored {
+                href.strip_prefix(prefix)
+            } else {
+                href.find(prefix).map(|pos| &href[pos + prefix.len()..])
+            };
+            if let Some(v) = v {
+                if let Ok(vers) = Version::parse(v.trim_end_matches('/')) {
+                    versions.push(vers);
+                }
+            }
+        }
+    }
+    versions.sort();
+    versions.reverse();
+    versions.dedup();
+    versions
+}
+
+/// A product on releases.hashicorp.com (`terraform`, `packer`, `vault`, ...): zip
+/// assets under `<name>/<version>/` with a gpg-signed SHA256SUMS.
+#[derive(Debug, Clone)]
+pub struct HashiCorp {
+    name: String,
+}
+
+impl HashiCorp {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+        }
+    }
+
+    /// Terraform itself.
+    pub fn terraform() -> Self {
+        Self::new("terraform")
+    }
+
+    fn is_terraform(&self) -> bool {
+        self.name == "terraform"
+    }
+}
+
+impl ProductBackend for HashiCorp {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn listing_url(&self) -> String {
+        remote_override(&self.name)
+            .unwrap_or_else(|| format!("https://releases.hashicorp.com/{}/", self.name))
+    }
+
+    fn parse_listing(&self, body: &str) -> Vec<Version> {
+        versions_linked(body, &format!("/{}/", self.name), true)
+    }
+
+    fn asset_name(&self, version: &str, os: &str, arch: &str) -> String {
+        release_asset_name(&self.name, version, os, arch, self.archive_format())
+    }
+
+    fn asset_url(&self, version: &str, asset: &str) -> String {
+        // HashiCorp releases: <base><version>/<asset>
+        let base = remote_base(
+            &self.name,
+            &format!("https://releases.hashicorp.com/{}/", self.name),
+        );
+        format!("{}{}/{}", base, version, asset)
+    }
+
+    fn asset_platform(&self, version: &str, asset: &str) -> Option<String> {
+        let platform = asset
+            .strip_prefix(&format!("{}_{}_", self.name, version))?
+            .strip_suffix(self.archive_format().extension())?;
+        Some(platform.to_string())
+    }
+
+    fn sha256sums_file(&self, version: &str) -> Option<String> {
+        Some(format!("{}_{}_SHA256SUMS", self.name, version))
+    }
+
+    fn signature_scheme(&self) -> SignatureScheme {
+        SignatureScheme::GpgDetached
+    }
+
+    fn version_args(&self) -> Vec<String> {
+        let args: &[&str] = if self.is_terraform() {
+            &["version", "-json"]
+        } else {
+            &["version"]
+        };
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn constraint_block(&self) -> Option<&str> {
+        // `terraform { ... }`, `packer { ... }`
+        Some(&self.name)
+    }
+
+    fn is_constraint_file(&self, file_name: &str) -> bool {
+        match self.name.as_str() {
+            "terraform" => file_name.ends_with(".tf") || file_name.ends_with(".tf.json"),
+            "packer" => file_name.ends_with(".pkr.hcl") || file_name.ends_with(".pkr.json"),
+            _ => false,
+        }
+    }
+
+    fn reads_terraform_state(&self) -> bool {
+        self.is_terraform()
+    }
+}
+
+/// OpenTofu from the GitHub releases of `opentofu/opentofu`, verified against their
+/// `tofu_<version>_SHA256SUMS`. It reads `.opentofu-version`, falling back to
+/// Terraform's version file, and shares `TFENV_TERRAFORM_VERSION`.
+#[derive(Debug, Clone, Default)]
+pub struct OpenTofu;
+
+impl ProductBackend for OpenTofu {
+    fn name(&self) -> &str {
+        "opentofu"
+    }
+
+    fn binary(&self) -> &str {
+        "tofu"
+    }
+
+    fn version_file(&self) -> String {
+        ".opentofu-version".to_string()
+    }
+
+    fn version_files(&self) -> Vec<String> {
+        vec![self.version_file(), ".terraform-version".to_string()]
+    }
+
+    fn version_env(&self) -> String {
+        "TFENV_TERRAFORM_VERSION".to_string()
+    }
+
+    fn listing_url(&self) -> String {
+        remote_override(self.name())
+            .unwrap_or_else(|| "https://github.com/opentofu/opentofu/releases".to_string())
+    }
+
+    fn parse_listing(&self, body: &str) -> Vec<Version> {
+        // look for GitHub release tag links like /opentofu/opentofu/releases/tag/vX.Y.Z
+        versions_linked(body, "/opentofu/opentofu/releases/tag/v", false)
+    }
+
+    fn asset_name(&self, version: &str, os: &str, arch: &str) -> String {
+        release_asset_name("tofu", version, os, arch, self.archive_format())
+    }
+
+    fn asset_url(&self, version: &str, asset: &str) -> String {
+        // GitHub-style releases download base: <base>v<version>/<asset>
+        let base = remote_base(
+            self.name(),
+            "https://github.com/opentofu/opentofu/releases/download/",
+        );
+        format!("{}v{}/{}", base, version, asset)
+    }
+
+    fn asset_platform(&self, version: &str, asset: &str) -> Option<String> {
+        let platform = asset
+            .strip_prefix(&format!("tofu_{}_", version))?
+            .strip_suffix(self.archive_format().extension())?;
+        Some(platform.to_string())
+    }
+
+    fn sha256sums_file(&self, version: &str) -> Option<String> {
+        Some(format!("tofu_{}_SHA256SUMS", version))
+    }
+
+    fn version_args(&self) -> Vec<String> {
+        vec!["version".to_string(), "-json".to_string()]
+    }
+
+    fn is_constraint_file(&self, file_name: &str) -> bool {
+        file_name.ends_with(".tf")
+            || file_name.ends_with(".tf.json")
+            || file_name.ends_with(".tofu")
+            || file_name.ends_with(".tofu.json")
+    }
+
+    fn reads_terraform_state(&self) -> bool {
+        true
+    }
+}
+
+/// Terragrunt from the GitHub releases of `gruntwork-io/terragrunt`: raw binaries
+/// and a `SHA256SUMS` asset. Pinned by `terragrunt_version_constraint` in
+/// `terragrunt.hcl` when no `.terragrunt-version` applies.
+#[derive(Debug, Clone, Default)]
+pub struct Terragrunt;
+
+impl ProductBackend for Terragrunt {
+    fn name(&self) -> &str {
+        "terragrunt"
+    }
+
+    fn listing_url(&self) -> String {
+        remote_override(self.name())
+            .unwrap_or_else(|| "https://github.com/gruntwork-io/terragrunt/releases".to_string())
+    }
+
+    fn parse_listing(&self, body: &str) -> Vec<Version> {
+        versions_linked(body, "/gruntwork-io/terragrunt/releases/tag/v", false)
+    }
+
+    fn archive_format(&self) -> ArchiveFormat {
+        ArchiveFormat::Binary
+    }
+
+    fn asset_name(&self, _version: &str, os: &str, arch: &str) -> String {
+        // unversioned raw binaries: terragrunt_<os>_<arch>[.exe]
+        let exe = if os == "windows" { ".exe" } else { "" };
+        format!("terragrunt_{}_{}{}", os, arch, exe)
+    }
+
+    fn asset_url(&self, version: &str, asset: &str) -> String {
+        let base = remote_base(
+            self.name(),
+            "https://github.com/gruntwork-io/terragrunt/releases/download/",
+        );
+        format!("{}v{}/{}", base, version, asset)
+    }
+
+    fn asset_platform(&self, _version: &str, asset: &str) -> Option<String> {
+        let rest = asset.strip_prefix("terragrunt_")?;
+        Some(rest.strip_suffix(".exe").unwrap_or(rest).to_string())
+    }
+
+    fn sha256sums_file(&self, _version: &str) -> Option<String> {
+        Some("SHA256SUMS".to_string())
+    }
+
+    fn version_args(&self) -> Vec<String> {
+        vec!["--version".to_string()]
+    }
+
+    fn constraint_attribute(&self) -> &str {
+        "terragrunt_version_constraint"
+    }
+
+    fn constraint_block(&self) -> Option<&str> {
+        None
+    }
+
+    fn is_constraint_file(&self, file_name: &str) -> bool {
+        file_name == "terragrunt.hcl"
+    }
+
+    fn resolves_from_constraint(&self) -> bool {
+        // Terragrunt has no other per-directory pin, so the constraint acts like
+        // `latest-allowed`
+        true
+    }
+}
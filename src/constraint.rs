@@ -0,0 +1,246 @@
+use anyhow::Result;
+use semver::{Prerelease, Version};
+use std::fmt;
+
+/// Comparison operator of a single constraint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    /// `~>`: at least the version, allowing only the rightmost given segment to grow
+    Pessimistic,
+}
+
+impl Op {
+    fn as_str(self) -> &'static str {
+        match self {
+            Op::Eq => "=",
+            Op::Ne => "!=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Pessimistic => "~>",
+        }
+    }
+}
+
+/// One `<op> <version>` term of a Terraform version constraint.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Constraint {
+    pub op: Op,
+    /// The version with missing segments filled with zeros
+    pub version: Version,
+    /// Number of segments written (`~> 1.5` has 2, `~> 1.5.0` has 3)
+    pub segments: usize,
+}
+
+impl Constraint {
+    /// Parse one term such as `>= 1.3`, `~>1.5.2` or `1.6.0-beta1`.
+    pub fn parse(term: &str) -> Result<Self> {
+        let term = term.trim();
+        let (op, rest) = [
+            ("~>", Op::Pessimistic),
+            (">=", Op::Ge),
+            ("<=", Op::Le),
+            ("!=", Op::Ne),
+            (">", Op::Gt),
+            ("<", Op::Lt),
+            ("=", Op::Eq),
+        ]
+        .iter()
+        .find_map(|(prefix, op)| term.strip_prefix(prefix).map(|rest| (*op, rest)))
+        .unwrap_or((Op::Eq, term));
+        let raw = rest.trim().trim_start_matches('v');
+        let (numbers, pre) = match raw.split_once('-') {
+            Some((numbers, pre)) => (numbers, Some(pre)),
+            None => (raw, None),
+        };
+        let parts: Vec<&str> = numbers.split('.').collect();
+        if parts.is_empty() || parts.len() > 3 {
+            anyhow::bail!("invalid version '{}' in constraint '{}'", raw, term);
+        }
+        let mut nums = [0u64; 3];
+        for (i, part) in parts.iter().enumerate() {
+            nums[i] = part.parse().map_err(|_| {
+                anyhow::anyhow!("invalid version '{}' in constraint '{}'", raw, term)
+            })?;
+        }
+        let mut version = Version::new(nums[0], nums[1], nums[2]);
+        if let Some(pre) = pre {
+            version.pre = Prerelease::new(pre)
+                .map_err(|_| anyhow::anyhow!("invalid prerelease in constraint '{}'", term))?;
+        }
+        Ok(Self {
+            op,
+            version,
+            segments: parts.len(),
+        })
+    }
+
+    /// Whether `v` satisfies this term, with Terraform's prerelease rules: a
+    /// prerelease only matches a term naming a prerelease of the same
+    /// `major.minor.patch`, and `~>` with a prerelease never matches a release.
+    pub fn matches(&self, v: &Version) -> bool {
+        let c = &self.version;
+        let v_pre = !v.pre.is_empty();
+        let c_pre = !c.pre.is_empty();
+        let same_core = (v.major, v.minor, v.patch) == (c.major, c.minor, c.patch);
+        if v_pre && !(c_pre && same_core) {
+            return false;
+        }
+        match self.op {
+            Op::Eq => v == c,
+            Op::Ne => v != c,
+            Op::Gt => v > c,
+            Op::Ge => v >= c,
+            Op::Lt => v < c,
+            Op::Le => v <= c,
+            Op::Pessimistic => {
+                if c_pre && !v_pre {
+                    return false;
+                }
+                if v < c {
+                    return false;
+                }
+                // every segment before the last written one must be equal; like
+                // Terraform (go-version), `~> 1` therefore only means `>= 1`
+                match self.segments {
+                    1 => true,
+                    2 => v.major == c.major,
+                    _ => v.major == c.major && v.minor == c.minor,
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let c = &self.version;
+        let mut version = match self.segments {
+            1 => c.major.to_string(),
+            2 => format!("{}.{}", c.major, c.minor),
+            _ => format!("{}.{}.{}", c.major, c.minor, c.patch),
+        };
+        if !c.pre.is_empty() {
+            version = format!("{}-{}", version, c.pre);
+        }
+        write!(f, "{} {}", self.op.as_str(), version)
+    }
+}
+
+/// Whether `spec` is a constraint expression (`~> 1.5`, `>= 1.5, < 1.7`) rather than
+/// a plain version or a keyword such as `latest:<regex>`.
+pub fn is_constraint_expression(spec: &str) -> bool {
+    let spec = spec.trim_start();
+    ["~>", ">", "<", "!=", "="]
+        .iter()
+        .any(|op| spec.starts_with(op))
+        || (spec.contains(',') && !spec.starts_with("latest"))
+}
+
+/// A set of terms that must all hold, e.g. `>= 1.3, < 1.6` (or the intersection of
+/// several `required_version` constraints).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Constraints {
+    pub terms: Vec<Constraint>,
+}
+
+/// Which satisfying version to select.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pick {
+    Min,
+    Max,
+}
+
+impl Constraints {
+    /// Parse a comma-separated constraint string.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let terms = spec
+            .split(',')
+            .filter(|t| !t.trim().is_empty())
+            .map(Constraint::parse)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { terms })
+    }
+
+    /// Add the terms of `other`; a version must then satisfy both.
+    pub fn intersect(&mut self, other: Constraints) {
+        self.terms.extend(other.terms);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Whether `v` satisfies every term. Without terms, only releases match.
+    pub fn matches(&self, v: &Version) -> bool {
+        if self.terms.is_empty() {
+            return v.pre.is_empty();
+        }
+        self.terms.iter().all(|t| t.matches(v))
+    }
+
+    /// The lowest or highest of `candidates` satisfying every term, or an error
+    /// explaining which terms ruled the candidates out.
+    pub fn select(&self, candidates: &[Version], pick: Pick) -> Result<Version> {
+        let matching = candidates.iter().filter(|v| self.matches(v));
+        let found = match pick {
+            Pick::Min => matching.min(),
+            Pick::Max => matching.max(),
+        };
+        if let Some(v) = found {
+            return Ok(v.clone());
+        }
+        anyhow::bail!("{}", self.explain_no_match(candidates))
+    }
+
+    fn explain_no_match(&self, candidates: &[Version]) -> String {
+        let releases: Vec<&Version> = candidates.iter().filter(|v| v.pre.is_empty()).collect();
+        if candidates.is_empty() {
+            return format!("no versions available to satisfy '{}'", self);
+        }
+        let mut lines = vec![format!(
+            "no version satisfies '{}' ({} candidates",
+            self,
+            candidates.len()
+        )];
+        if let (Some(oldest), Some(newest)) = (releases.iter().min(), releases.iter().max()) {
+            lines[0].push_str(&format!(", releases {} to {}", oldest, newest));
+        }
+        lines[0].push(')');
+        for term in &self.terms {
+            let allowed = candidates.iter().filter(|v| term.matches(v)).count();
+            lines.push(format!(
+                "  '{}' allows {} of {} candidates",
+                term,
+                allowed,
+                candidates.len()
+            ));
+        }
+        if self.terms.len() > 1
+            && self
+                .terms
+                .iter()
+                .all(|t| candidates.iter().any(|v| t.matches(v)))
+        {
+            lines.push(
+                "  each term is satisfiable alone, but no candidate satisfies all of them"
+                    .to_string(),
+            );
+        }
+        lines.join("\n")
+    }
+}
+
+impl fmt::Display for Constraints {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let terms: Vec<String> = self.terms.iter().map(|t| t.to_string()).collect();
+        write!(f, "{}", terms.join(", "))
+    }
+}
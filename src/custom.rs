@@ -0,0 +1,309 @@
+use crate::archive::ArchiveFormat;
+use crate::backend::ProductBackend;
+use crate::installer::release_platforms;
+use crate::product::register_backend;
+use anyhow::{Context, Result};
+use regex::Regex;
+use semver::Version;
+use serde::Deserialize;
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Products defined in `products.json`:
+///
+/// ```json
+/// { "products": [ {
+///     "name": "tflint",
+///     "listing_url": "https://api.github.com/repos/terraform-linters/tflint/releases",
+///     "listing_json_path": "[*].tag_name",
+///     "asset": "tflint_{os}_{arch}.zip",
+///     "asset_url": "https://github.com/terraform-linters/tflint/releases/download/v{version}/{asset}",
+///     "checksums_url": "https://github.com/terraform-linters/tflint/releases/download/v{version}/checksums.txt",
+///     "version_args": ["--version"]
+/// } ] }
+/// ```
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProductsFile {
+    products: Vec<CustomProduct>,
+}
+
+/// A product described declaratively rather than in Rust. Templates may use
+/// `{version}`, `{os}`, `{arch}` and `{exe}` (`.exe` for Windows); `asset_url` and
+/// `checksums_url` may also use `{asset}`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomProduct {
+    pub name: String,
+    /// Executable in the asset, without `.exe` (default: `name`)
+    pub binary: Option<String>,
+    /// Version file name (default: `.<name>-version`)
+    pub version_file: Option<String>,
+    /// Page or API endpoint listing the releases
+    pub listing_url: String,
+    /// Path to the versions in a JSON listing, e.g. `[*].tag_name` or `versions.*.version`
+    pub listing_json_path: Option<String>,
+    /// Regex over the listing body; the `version` group (or the first group) is the version
+    pub listing_regex: Option<String>,
+    /// Asset file name template
+    pub asset: String,
+    /// Asset download URL template
+    pub asset_url: String,
+    /// SHA256SUMS-style checksum file URL template, per release or, using `{asset}`,
+    /// `{os}`, `{arch}` or `{exe}`, per asset (default: no verification)
+    pub checksums_url: Option<String>,
+    /// How the asset is extracted: `zip`, `tar.gz` or `binary` (default: from the
+    /// asset's extension, else `binary`)
+    pub archive_format: Option<String>,
+    /// Arguments making the binary print its version (default: `version`)
+    pub version_args: Option<Vec<String>>,
+}
+
+impl CustomProduct {
+    fn validate(self) -> Result<Self> {
+        // lowercase, as `--product`/`TFENV_PRODUCT` are looked up lowercased
+        if self.name.is_empty()
+            || !self
+                .name
+                .chars()
+                .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        {
+            anyhow::bail!(
+                "invalid product name '{}' (use lowercase letters, digits and '-')",
+                self.name
+            );
+        }
+        match (&self.listing_json_path, &self.listing_regex) {
+            (Some(_), Some(_)) => anyhow::bail!(
+                "product '{}': set only one of listing_json_path and listing_regex",
+                self.name
+            ),
+            (None, Some(re)) => {
+                Regex::new(re)
+                    .with_context(|| format!("product '{}': invalid listing_regex", self.name))?;
+            }
+            _ => {}
+        }
+        if let Some(format) = &self.archive_format {
+            if ArchiveFormat::parse(format).is_none() {
+                anyhow::bail!(
+                    "product '{}': unknown archive_format '{}'",
+                    self.name,
+                    format
+                );
+            }
+        }
+        Ok(self)
+    }
+
+    fn render(&self, template: &str, version: &str, os: &str, arch: &str) -> String {
+        template
+            .replace("{version}", version)
+            .replace("{os}", os)
+            .replace("{arch}", arch)
+            .replace("{exe}", if os == "windows" { ".exe" } else { "" })
+    }
+
+    /// Whether `checksums_url` names a checksum file per asset rather than per release.
+    fn checksums_per_asset(&self) -> bool {
+        self.checksums_url.as_deref().is_some_and(|t| {
+            ["{asset}", "{os}", "{arch}", "{exe}"]
+                .iter()
+                .any(|p| t.contains(p))
+        })
+    }
+
+    /// `template` rendered for the platform `asset` was built for, and `asset` itself.
+    fn render_for_asset(&self, template: &str, version: &str, asset: &str) -> String {
+        let (os, arch) = self.platform_of(version, asset).unwrap_or(("", ""));
+        self.render(template, version, os, arch)
+            .replace("{asset}", asset)
+    }
+
+    /// The `(os, arch)` an asset name was rendered for.
+    fn platform_of(&self, version: &str, asset: &str) -> Option<(&'static str, &'static str)> {
+        release_platforms()
+            .into_iter()
+            .find(|(os, arch)| self.asset_name(version, os, arch) == asset)
+    }
+}
+
+/// Values at `path` in `json`: dot-separated keys, where `*` or `[*]` expands every
+/// element of an array (or value of an object) and `[n]` selects one element.
+pub fn json_path_values<'a>(json: &'a Value, path: &str) -> Vec<&'a Value> {
+    let mut current = vec![json];
+    let path = path.trim_start_matches('$');
+    let segments = path
+        .replace('[', ".[")
+        .split('.')
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    for segment in segments {
+        let mut next = Vec::new();
+        for value in current {
+            match segment.as_str() {
+                "*" | "[*]" => match value {
+                    Value::Array(items) => next.extend(items.iter()),
+                    Value::Object(map) => next.extend(map.values()),
+                    _ => {}
+                },
+                s if s.starts_with('[') && s.ends_with(']') => {
+                    if let Some(v) = s[1..s.len() - 1]
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|i| value.get(i))
+                    {
+                        next.push(v);
+                    }
+                }
+                key => next.extend(value.get(key)),
+            }
+        }
+        current = next;
+    }
+    current
+}
+
+impl ProductBackend for CustomProduct {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn binary(&self) -> &str {
+        self.binary.as_deref().unwrap_or(&self.name)
+    }
+
+    fn version_file(&self) -> String {
+        self.version_file
+            .clone()
+            .unwrap_or_else(|| format!(".{}-version", self.name))
+    }
+
+    fn listing_url(&self) -> String {
+        self.listing_url.clone()
+    }
+
+    fn parse_listing(&self, body: &str) -> Vec<Version> {
+        let raw: Vec<String> = if let Some(path) = &self.listing_json_path {
+            match serde_json::from_str::<Value>(body) {
+                Ok(json) => json_path_values(&json, path)
+                    .into_iter()
+                    .filter_map(|v| v.as_str().map(str::to_string))
+                    .collect(),
+                Err(_) => Vec::new(),
+            }
+        } else {
+            // validated in `validate`
+            let re = Regex::new(
+                self.listing_regex
+                    .as_deref()
+                    .unwrap_or(r"(?P<version>[0-9]+\.[0-9]+\.[0-9]+(?:-[0-9A-Za-z.]+)?)"),
+            )
+            .unwrap();
+            re.captures_iter(body)
+                .filter_map(|c| c.name("version").or_else(|| c.get(1)))
+                .map(|m| m.as_str().to_string())
+                .collect()
+        };
+        let mut versions: Vec<Version> = raw
+            .iter()
+            .filter_map(|v| Version::parse(v.trim().trim_start_matches('v')).ok())
+            .collect();
+        versions.sort();
+        versions.reverse();
+        versions.dedup();
+        versions
+    }
+
+    fn archive_format(&self) -> ArchiveFormat {
+        if let Some(format) = self
+            .archive_format
+            .as_deref()
+            .and_then(ArchiveFormat::parse)
+        {
+            return format;
+        }
+        if self.asset.ends_with(".zip") {
+            ArchiveFormat::Zip
+        } else if self.asset.ends_with(".tar.gz") || self.asset.ends_with(".tgz") {
+            ArchiveFormat::TarGz
+        } else {
+            ArchiveFormat::Binary
+        }
+    }
+
+    fn asset_name(&self, version: &str, os: &str, arch: &str) -> String {
+        self.render(&self.asset, version, os, arch)
+    }
+
+    fn asset_url(&self, version: &str, asset: &str) -> String {
+        self.render_for_asset(&self.asset_url, version, asset)
+    }
+
+    fn asset_platform(&self, version: &str, asset: &str) -> Option<String> {
+        self.platform_of(version, asset)
+            .map(|(os, arch)| format!("{}_{}", os, arch))
+    }
+
+    fn sha256sums_file(&self, version: &str) -> Option<String> {
+        let url = self.sha256sums_url(version)?;
+        url.rsplit('/').next().map(str::to_string)
+    }
+
+    fn sha256sums_url(&self, version: &str) -> Option<String> {
+        if self.checksums_per_asset() {
+            return None;
+        }
+        self.checksums_url
+            .as_ref()
+            .map(|t| self.render(t, version, "", ""))
+    }
+
+    fn asset_sha256sums_url(&self, version: &str, asset: &str) -> Option<String> {
+        self.checksums_url
+            .as_ref()
+            .map(|t| self.render_for_asset(t, version, asset))
+    }
+
+    fn version_args(&self) -> Vec<String> {
+        self.version_args
+            .clone()
+            .unwrap_or_else(|| vec!["version".to_string()])
+    }
+}
+
+/// Parse a `products.json` document.
+pub fn parse_custom_products(body: &str) -> Result<Vec<CustomProduct>> {
+    let file: ProductsFile = serde_json::from_str(body).context("invalid products file")?;
+    file.products
+        .into_iter()
+        .map(CustomProduct::validate)
+        .collect()
+}
+
+/// `TFENV_PRODUCTS_FILE`, else `<config dir>/products.json`.
+pub fn products_file(config_dir: &Path) -> PathBuf {
+    env::var("TFENV_PRODUCTS_FILE")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| config_dir.join("products.json"))
+}
+
+/// Register the products defined in the products file, if there is one. They
+/// take precedence over built-in products of the same name.
+pub fn register_custom_products(config_dir: &Path) -> Result<()> {
+    let path = products_file(config_dir);
+    if !path.exists() {
+        return Ok(());
+    }
+    let body =
+        fs::read_to_string(&path).with_context(|| format!("failed to read {}", path.display()))?;
+    let products =
+        parse_custom_products(&body).with_context(|| format!("in {}", path.display()))?;
+    for product in products {
+        register_backend(product);
+    }
+    Ok(())
+}
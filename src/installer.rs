@@ -1,29 +1,134 @@
+use crate::archive::{extract_archive, ArchiveFormat};
+use crate::backend::SignatureScheme;
+use crate::product::Product;
 use anyhow::{Context, Result};
+use regex::Regex;
 use reqwest::blocking::Client;
 use sha2::{Digest, Sha256};
+use std::collections::HashMap;
 use std::env;
-use std::fs::{self, File};
-use std::io::{copy, Read, Write};
-#[cfg(unix)]
-use std::os::unix::fs::PermissionsExt;
-use std::path::Path;
+use std::fs::{self, File, OpenOptions};
+use std::io::{copy, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, OnceLock};
+use std::thread;
+use std::time::{Duration, SystemTime};
 use tempfile::NamedTempFile;
 
+/// Name of the file recording how a version directory was installed.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Rust `std::env::consts::OS` values and the OS names used in release asset names.
+const OS_NAMES: &[(&str, &str)] = &[
+    ("linux", "linux"),
+    ("macos", "darwin"),
+    ("windows", "windows"),
+    ("freebsd", "freebsd"),
+    ("openbsd", "openbsd"),
+    ("solaris", "solaris"),
+    ("illumos", "solaris"),
+];
+
+/// Rust `std::env::consts::ARCH` values and the architecture names used in release
+/// asset names. HashiCorp only ships little-endian ppc64 builds.
+const ARCH_NAMES: &[(&str, &str)] = &[
+    ("x86_64", "amd64"),
+    ("x86", "386"),
+    ("aarch64", "arm64"),
+    ("arm", "arm"),
+    ("powerpc64", "ppc64le"),
+    ("s390x", "s390x"),
+];
+
+fn lookup_platform_name(table: &[(&str, &'static str)], name: &str) -> Option<&'static str> {
+    let name = name.trim().to_lowercase();
+    table
+        .iter()
+        .find(|(rust, release)| *rust == name || *release == name)
+        .map(|(_, release)| *release)
+}
+
+/// Release OS name for a Rust or release-style OS name (`macos` and `darwin` both
+/// give `darwin`), or `None` if no releases exist for it.
+pub fn os_name(name: &str) -> Option<&'static str> {
+    lookup_platform_name(OS_NAMES, name)
+}
+
+/// Release architecture name for a Rust or release-style arch name (`x86` and
+/// `386` both give `386`), or `None` if no releases exist for it.
+pub fn arch_name(name: &str) -> Option<&'static str> {
+    lookup_platform_name(ARCH_NAMES, name)
+}
+
+/// Check `TFENV_OS`/`TFENV_ARCH` name platforms that releases exist for and that
+/// `TFENV_ARCHIVE_FORMAT` is a known format.
+pub fn validate_env_overrides() -> Result<()> {
+    if let Ok(format) = env::var("TFENV_ARCHIVE_FORMAT") {
+        if ArchiveFormat::parse(&format).is_none() {
+            anyhow::bail!(
+                "unsupported TFENV_ARCHIVE_FORMAT '{}'; expected zip, tar.gz or binary",
+                format
+            );
+        }
+    }
+    if let Ok(os) = env::var("TFENV_OS") {
+        if os_name(&os).is_none() {
+            anyhow::bail!(
+                "unsupported TFENV_OS '{}'; expected one of: {}",
+                os,
+                supported_names(OS_NAMES)
+            );
+        }
+    }
+    if let Ok(arch) = env::var("TFENV_ARCH") {
+        if arch_name(&arch).is_none() {
+            anyhow::bail!(
+                "unsupported TFENV_ARCH '{}'; expected one of: {}",
+                arch,
+                supported_names(ARCH_NAMES)
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Every `(os, arch)` pair in release naming, whether or not a product ships it.
+pub fn release_platforms() -> Vec<(&'static str, &'static str)> {
+    let mut platforms = Vec::new();
+    for (_, os) in OS_NAMES {
+        for (_, arch) in ARCH_NAMES {
+            if !platforms.contains(&(*os, *arch)) {
+                platforms.push((*os, *arch));
+            }
+        }
+    }
+    platforms
+}
+
+fn supported_names(table: &[(&str, &str)]) -> String {
+    let mut names: Vec<&str> = table.iter().map(|(_, release)| *release).collect();
+    names.dedup();
+    names.join(", ")
+}
+
+/// OS name for release assets: `TFENV_OS` if set, otherwise this host's OS.
 pub fn map_os() -> &'static str {
-    match env::consts::OS {
-        "macos" => "darwin",
-        other => other,
+    if let Some(os) = env::var("TFENV_OS").ok().and_then(|o| os_name(&o)) {
+        return os;
     }
+    os_name(env::consts::OS).unwrap_or(env::consts::OS)
 }
 
+/// Architecture name for release assets: `TFENV_ARCH` if set, otherwise this host's.
 pub fn map_arch() -> &'static str {
-    match env::consts::ARCH {
-        "x86_64" => "amd64",
-        "aarch64" => "arm64",
-        other => other,
+    if let Some(arch) = env::var("TFENV_ARCH").ok().and_then(|a| arch_name(&a)) {
+        return arch;
     }
+    arch_name(env::consts::ARCH).unwrap_or(env::consts::ARCH)
 }
 
+/// File name of the Terraform binary on this host.
+#[deprecated(note = "use `Product::binary_name`")]
 pub fn terraform_binary_name() -> &'static str {
     if cfg!(windows) {
         "terraform.exe"
@@ -32,29 +137,32 @@ pub fn terraform_binary_name() -> &'static str {
     }
 }
 
+/// Release asset of `product` for this host, e.g. `terraform_1.6.3_linux_amd64.zip`.
+#[deprecated(note = "use `Product::asset_name`")]
 pub fn asset_name(product: &str, version: &str) -> String {
-    let os = map_os();
-    let arch = map_arch();
-    format!("{}_{}_{}_{}.zip", product, version, os, arch)
+    match Product::from_name(product) {
+        Ok(product) => product.asset_name(version, map_os(), map_arch()),
+        Err(_) => format!("{}_{}_{}_{}.zip", product, version, map_os(), map_arch()),
+    }
 }
 
-fn asset_url(product: &str, remote: &str, version: &str, asset: &str) -> String {
-    let base = if remote.ends_with('/') {
-        remote.to_string()
-    } else {
-        format!("{}/", remote)
-    };
-    if product == "terraform" {
-        // HashiCorp releases: <base><version>/<asset>
-        format!("{}{}{}", base, version, "/") + asset
-    } else {
-        // Assume GitHub-style releases download base: <base>v<version>/<asset>
-        format!("{}v{}/{}", base, version, asset)
+static HTTP_CLIENT: OnceLock<Client> = OnceLock::new();
+
+/// Process-wide HTTP client, so parallel installs share one connection pool. It
+/// identifies itself, as the GitHub API rejects requests without a `User-Agent`.
+pub fn http_client() -> Result<Client> {
+    if let Some(client) = HTTP_CLIENT.get() {
+        return Ok(client.clone());
     }
+    let client = Client::builder()
+        .user_agent(concat!("tfenv-rs/", env!("CARGO_PKG_VERSION")))
+        .build()
+        .context("failed to build HTTP client")?;
+    Ok(HTTP_CLIENT.get_or_init(|| client).clone())
 }
 
 fn fetch_to_temp(url: &str) -> Result<NamedTempFile> {
-    let client = Client::builder().build()?;
+    let client = http_client()?;
     let mut resp = client.get(url).send().context("failed to fetch asset")?;
     if !resp.status().is_success() {
         anyhow::bail!("Failed to download {}: HTTP {}", url, resp.status());
@@ -64,21 +172,16 @@ fn fetch_to_temp(url: &str) -> Result<NamedTempFile> {
     Ok(tmp)
 }
 
-fn fetch_sha256sums(_remote: &str, version: &str) -> Result<String> {
-    let client = Client::builder().build()?;
-    // The above is brittle; try canonical HashiCorp path
-    let candidate = format!(
-        "https://releases.hashicorp.com/terraform/{}/terraform_{}_SHA256SUMS",
-        version, version
-    );
+fn fetch_sha256sums(url: &str) -> Result<String> {
+    let client = http_client()?;
     let mut resp = client
-        .get(&candidate)
+        .get(url)
         .send()
-        .context("failed to fetch sha256sums")?;
+        .with_context(|| format!("failed to fetch {}", url))?;
     if !resp.status().is_success() {
         anyhow::bail!(
             "Failed to fetch SHA256SUMS: {} status: {}",
-            candidate,
+            url,
             resp.status()
         );
     }
@@ -87,14 +190,10 @@ fn fetch_sha256sums(_remote: &str, version: &str) -> Result<String> {
     Ok(body)
 }
 
-fn fetch_sig(_remote: &str, version: &str) -> Result<NamedTempFile> {
-    let client = Client::builder().build()?;
-    let candidate = format!(
-        "https://releases.hashicorp.com/terraform/{}/terraform_{}_SHA256SUMS.sig",
-        version, version
-    );
+fn fetch_sig(candidate: &str) -> Result<NamedTempFile> {
+    let client = http_client()?;
     let mut resp = client
-        .get(&candidate)
+        .get(candidate)
         .send()
         .context("failed to fetch sha256sig")?;
     if !resp.status().is_success() {
@@ -142,6 +241,28 @@ fn verify_sig_with_gpg(tfenv_root: &Path, sig_path: &Path, sums_path: &Path) ->
     Ok(())
 }
 
+/// Validate a user-supplied SHA-256 digest and return it in lowercase hex.
+pub fn normalize_sha256(hex: &str) -> Result<String> {
+    if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        anyhow::bail!("'{}' is not a 64-character hex SHA-256 digest", hex);
+    }
+    Ok(hex.to_ascii_lowercase())
+}
+
+/// Check `path` against checksums pinned on the command line or in a version file.
+fn verify_pinned_sha256(path: &Path, pinned: &[String]) -> Result<()> {
+    let actual = compute_sha256(path)?;
+    if !pinned.iter().any(|p| p.eq_ignore_ascii_case(&actual)) {
+        anyhow::bail!(
+            "SHA256 mismatch: archive hashes to {} but the pinned checksum is {}",
+            actual,
+            pinned.join(" or ")
+        );
+    }
+    println!("Pinned checksum verified");
+    Ok(())
+}
+
 fn compute_sha256(path: &Path) -> Result<String> {
     let mut f = File::open(path).context("failed to open downloaded file for hashing")?;
     let mut hasher = Sha256::new();
@@ -156,99 +277,487 @@ fn compute_sha256(path: &Path) -> Result<String> {
     Ok(hex::encode(hasher.finalize()))
 }
 
-fn extract_zip_to_version(zip_path: &Path, versions_dir: &Path, version: &str) -> Result<()> {
-    let file = File::open(zip_path).context("failed to open zip file for extraction")?;
-    let mut archive = zip::ZipArchive::new(file).context("failed to read zip archive")?;
-    let out_dir = versions_dir.join(version);
-    fs::create_dir_all(&out_dir)?;
-    for i in 0..archive.len() {
-        let mut entry = archive.by_index(i).context("zip entry error")?;
-        let name = entry.name().to_string();
-        // We expect a single 'terraform' binary at top-level (or terraform.exe on Windows)
-        let binary_name = terraform_binary_name();
-        if name.ends_with(binary_name) || name.ends_with("terraform") {
-            let out_path = out_dir.join(terraform_binary_name());
-            let mut outfile =
-                File::create(&out_path).context("failed to create terraform output file")?;
-            copy(&mut entry, &mut outfile)?;
-            #[cfg(unix)]
+/// Extract a version's first `x.y.z[-pre]` token from `<binary> version` text output.
+/// Used for products without JSON output and Terraform releases older than 0.13.
+pub fn parse_plain_version_output(stdout: &str) -> Option<String> {
+    let re = Regex::new(r"v?([0-9]+\.[0-9]+\.[0-9]+(?:-[0-9A-Za-z.]+)?)").ok()?;
+    re.captures(stdout.lines().next()?)
+        .and_then(|c| c.get(1))
+        .map(|m| m.as_str().to_string())
+}
+
+/// Run `<binary> version [-json]` in a scratch directory with an empty HOME and make
+/// sure the binary executes and reports the version we meant to install.
+fn smoke_test_binary(binary: &Path, version: &str, args: &[String]) -> Result<()> {
+    let home = tempfile::TempDir::new().context("failed to create tempdir for smoke test")?;
+    let mut cmd = std::process::Command::new(binary);
+    cmd.args(args)
+        .env_clear()
+        .env("HOME", home.path())
+        .env("USERPROFILE", home.path())
+        .env("CHECKPOINT_DISABLE", "1")
+        .current_dir(home.path());
+    // Windows cannot start most processes without SystemRoot
+    if let Ok(root) = env::var("SYSTEMROOT") {
+        cmd.env("SYSTEMROOT", root);
+    }
+    let output = cmd.output().with_context(|| {
+        format!(
+            "failed to execute {} (is it built for this platform?)",
+            binary.display()
+        )
+    })?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "`{} {}` exited with {}: {}",
+            binary.display(),
+            args.join(" "),
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let reported = match serde_json::from_slice::<serde_json::Value>(&output.stdout) {
+        Ok(json) => ["terraform_version", "version"]
+            .iter()
+            .find_map(|key| json.get(key).and_then(|v| v.as_str()))
+            .map(|v| v.to_string()),
+        Err(_) => parse_plain_version_output(&String::from_utf8_lossy(&output.stdout)),
+    };
+    let reported = reported.ok_or_else(|| {
+        anyhow::anyhow!(
+            "could not read the version reported by {}",
+            binary.display()
+        )
+    })?;
+    if reported.trim_start_matches('v') != version {
+        anyhow::bail!(
+            "binary reports version {} but {} was requested",
+            reported,
+            version
+        );
+    }
+    Ok(())
+}
+
+/// Parse a SHA256SUMS body (`<hex>  <file>` or `<hex> *<file>` per line) into a
+/// map of file name to lowercase digest. Malformed lines and duplicate file names
+/// are errors rather than being skipped.
+pub fn parse_sha256sums(body: &str) -> Result<HashMap<String, String>> {
+    let mut sums = HashMap::new();
+    for (i, line) in body.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            continue;
+        }
+        let (hex, rest) = line
+            .split_once(char::is_whitespace)
+            .ok_or_else(|| anyhow::anyhow!("line {}: expected '<sha256>  <file>'", i + 1))?;
+        let hex = normalize_sha256(hex).with_context(|| format!("line {}", i + 1))?;
+        // one separator space, then an optional '*' binary-mode marker
+        let name = rest.strip_prefix(' ').unwrap_or(rest);
+        let name = name.strip_prefix('*').unwrap_or(name);
+        if name.is_empty() || name.trim() != name {
+            anyhow::bail!("line {}: invalid file name '{}'", i + 1, rest);
+        }
+        if sums.insert(name.to_string(), hex).is_some() {
+            anyhow::bail!("line {}: duplicate entry for {}", i + 1, name);
+        }
+    }
+    Ok(sums)
+}
+
+/// Check `path` against the entry for `asset` in the SHA256SUMS body fetched from
+/// `sums_source` (a URL or local path, used in error messages).
+fn verify_checksum(sums: &str, sums_source: &str, asset: &str, path: &Path) -> Result<()> {
+    let parsed = parse_sha256sums(sums)
+        .with_context(|| format!("malformed checksum file {}", sums_source))?;
+    let expected = parsed.get(asset).ok_or_else(|| {
+        anyhow::anyhow!("No checksum found for asset {} in {}", asset, sums_source)
+    })?;
+
+    let actual = compute_sha256(path)?;
+    if &actual != expected {
+        anyhow::bail!(
+            "SHA256 mismatch for {}: {} lists {} but the download hashes to {}",
+            asset,
+            sums_source,
+            expected,
+            actual
+        );
+    }
+    Ok(())
+}
+
+/// How long a lock file may sit untouched before it is assumed to belong to a
+/// killed process and is removed. Live holders touch it every
+/// [`INSTALL_LOCK_REFRESH`], however long their download takes.
+const INSTALL_LOCK_STALE_AFTER: Duration = Duration::from_secs(5 * 60);
+
+/// How often a held lock's modification time is refreshed.
+const INSTALL_LOCK_REFRESH: Duration = Duration::from_secs(30);
+
+/// Exclusive per-version lock (`versions/.<version>.lock`) held for the whole
+/// download/verify/extract of one version, so concurrent installs of the same
+/// version wait for each other instead of racing.
+pub struct InstallLock {
+    path: PathBuf,
+    /// Dropped to stop the thread keeping the lock fresh
+    stop: Option<mpsc::Sender<()>>,
+    heartbeat: Option<thread::JoinHandle<()>>,
+}
+
+impl InstallLock {
+    /// Wait for and take the lock of `version` in `versions_dir`.
+    pub fn acquire(versions_dir: &Path, version: &str) -> Result<Self> {
+        let path = versions_dir.join(format!(".{}.lock", version));
+        let mut announced = false;
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => return Ok(Self::held(path, file)),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    if is_stale_lock(&path) {
+                        remove_stale_lock(versions_dir, &path);
+                        continue;
+                    }
+                    if !announced {
+                        println!("Waiting for another install of {} to finish", version);
+                        announced = true;
+                    }
+                    thread::sleep(Duration::from_millis(200));
+                }
+                Err(e) => {
+                    return Err(e)
+                        .with_context(|| format!("failed to create lock {}", path.display()))
+                }
+            }
+        }
+    }
+
+    /// Start touching the lock file until the lock is dropped.
+    fn held(path: PathBuf, file: File) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let heartbeat = thread::spawn(move || {
+            while let Err(mpsc::RecvTimeoutError::Timeout) =
+                stopped.recv_timeout(INSTALL_LOCK_REFRESH)
             {
-                let mut perms = outfile.metadata()?.permissions();
-                perms.set_mode(0o755);
-                fs::set_permissions(&out_path, perms)?;
+                let _ = file.set_modified(SystemTime::now());
             }
-            return Ok(());
+        });
+        Self {
+            path,
+            stop: Some(stop),
+            heartbeat: Some(heartbeat),
         }
     }
-    anyhow::bail!("terraform binary not found inside archive");
 }
 
-pub fn install_version(
+fn is_stale_lock(path: &Path) -> bool {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.elapsed().ok())
+        .is_some_and(|age| age > INSTALL_LOCK_STALE_AFTER)
+}
+
+/// Remove the stale lock at `path`. It is first renamed aside, which only one of
+/// several waiters can do; a waiter that renamed a fresh lock instead (created after
+/// another one removed the stale lock) links it back without overwriting.
+fn remove_stale_lock(versions_dir: &Path, path: &Path) {
+    let Ok(aside) = tempfile::Builder::new()
+        .prefix(".stale-lock")
+        .tempfile_in(versions_dir)
+        .map(NamedTempFile::into_temp_path)
+    else {
+        return;
+    };
+    if fs::rename(path, &aside).is_ok() && !is_stale_lock(&aside) {
+        let _ = fs::hard_link(&aside, path);
+    }
+}
+
+impl Drop for InstallLock {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(heartbeat) = self.heartbeat.take() {
+            let _ = heartbeat.join();
+        }
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// What `install_version` did for the requested version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InstallStatus {
+    Installed,
+    AlreadyInstalled,
+}
+
+/// Extract a verified archive into `versions/<version>`, smoke test the binary and
+/// record where it came from and the files it contained in `manifest.json`.
+/// Shared by network and local installs.
+fn install_archive(
+    config_dir: &Path,
+    product: &Product,
+    version: &str,
+    archive: &Path,
+    format: ArchiveFormat,
+    source: &str,
+) -> Result<()> {
+    let versions_dir = product.versions_dir(config_dir);
+    fs::create_dir_all(&versions_dir)?;
+    // Extract into a hidden staging directory so a failed smoke test leaves nothing behind
+    let staging = tempfile::Builder::new()
+        .prefix(".installing-")
+        .tempdir_in(&versions_dir)
+        .context("failed to create staging directory")?;
+    let binary_name = product.binary_name();
+    let files = extract_archive(archive, staging.path(), &binary_name, format)?;
+    smoke_test_binary(
+        &staging.path().join(&binary_name),
+        version,
+        &product.version_args(),
+    )
+    .with_context(|| {
+        format!(
+            "post-install check failed for {}; installation rolled back",
+            version
+        )
+    })?;
+    let manifest = serde_json::json!({
+        "product": product.name(),
+        "version": version,
+        "source": source,
+        "sha256": compute_sha256(archive)?,
+        "files": files,
+    });
+    fs::write(
+        staging.path().join(MANIFEST_FILE),
+        serde_json::to_string_pretty(&manifest)?,
+    )
+    .context("failed to write version manifest")?;
+    let dest = versions_dir.join(version);
+    if dest.exists() {
+        fs::remove_dir_all(&dest).context("failed to replace existing version directory")?;
+    }
+    fs::rename(staging.path(), &dest).context("failed to move staged version into place")?;
+    println!(
+        "Installed {} {} to {}",
+        product.name(),
+        version,
+        dest.display()
+    );
+    Ok(())
+}
+
+/// Check an installed version against pinned checksums: the archive it was installed
+/// from must hash to one of them, as recorded in its `manifest.json`. Versions
+/// installed without a manifest cannot be verified and are rejected.
+pub fn verify_installed_sha256(
+    config_dir: &Path,
+    product: &Product,
+    version: &str,
+    pinned: &[String],
+) -> Result<()> {
+    let manifest = product
+        .versions_dir(config_dir)
+        .join(version)
+        .join(MANIFEST_FILE);
+    let recorded = fs::read_to_string(&manifest)
+        .ok()
+        .and_then(|body| serde_json::from_str::<serde_json::Value>(&body).ok())
+        .and_then(|json| json.get("sha256")?.as_str().map(str::to_string))
+        .ok_or_else(|| {
+            anyhow::anyhow!(
+                "cannot verify the pinned checksum of installed {} {}: {} is missing or records no sha256; reinstall it",
+                product.name(),
+                version,
+                manifest.display()
+            )
+        })?;
+    if !pinned.iter().any(|p| p.eq_ignore_ascii_case(&recorded)) {
+        anyhow::bail!(
+            "SHA256 mismatch: installed {} {} came from an archive hashing to {} but the pinned checksum is {}",
+            product.name(),
+            version,
+            recorded,
+            pinned.join(" or ")
+        );
+    }
+    Ok(())
+}
+
+/// Product and version inferred from a release file name such as
+/// `terraform_1.6.3_linux_amd64.zip`.
+pub fn parse_archive_name(file_name: &str) -> Option<(String, String)> {
+    let re = Regex::new(
+        r"^(?P<product>[A-Za-z0-9-]+)_v?(?P<version>[0-9]+\.[0-9]+\.[0-9]+(?:-[0-9A-Za-z.]+)?)_[A-Za-z0-9]+_[A-Za-z0-9]+\.(?:zip|tar\.gz|tgz)$",
+    )
+    .ok()?;
+    let caps = re.captures(file_name)?;
+    Some((caps["product"].to_lowercase(), caps["version"].to_string()))
+}
+
+/// Install a release archive that is already on disk (air-gapped hosts).
+///
+/// Product and version come from the arguments or, failing that, the file name.
+/// A SHA256SUMS file is taken from `sums` or looked up next to the archive as
+/// the product's SHA256SUMS file name; a `<sums>.sig` beside it is verified with gpg.
+/// Checksums in `pinned_sha256` are always checked, SUMS file or not.
+pub fn install_from_archive(
     tfenv_root: &Path,
     config_dir: &Path,
-    requested: Option<&str>,
+    archive: &Path,
+    product: Option<&str>,
+    version: Option<&str>,
+    sums: Option<&Path>,
+    pinned_sha256: &[String],
 ) -> Result<()> {
-    let version = if let Some(v) = requested {
-        v.to_string()
-    } else {
-        "latest".to_string()
+    if !archive.is_file() {
+        anyhow::bail!("archive {} does not exist", archive.display());
+    }
+    let file_name = archive
+        .file_name()
+        .and_then(|n| n.to_str())
+        .ok_or_else(|| anyhow::anyhow!("invalid archive path {}", archive.display()))?;
+    let inferred = parse_archive_name(file_name);
+    let product = match (product, &inferred) {
+        (Some(p), _) => Product::from_name(p)?,
+        (None, Some((p, _))) => Product::from_name(p)?,
+        (None, None) => anyhow::bail!("cannot infer product from '{}'; pass --product", file_name),
     };
-    // If requested is "latest", resolve remote latest - for MVP we'll treat "latest" as error
-    if version == "latest" {
-        anyhow::bail!("'latest' resolution not implemented in installer; pass an explicit version");
+    let version = match (version, &inferred) {
+        (Some(v), _) => v.trim_start_matches('v').to_string(),
+        (None, Some((_, v))) => v.clone(),
+        (None, None) => anyhow::bail!(
+            "cannot infer version from '{}'; pass the version explicitly",
+            file_name
+        ),
+    };
+
+    if !pinned_sha256.is_empty() {
+        verify_pinned_sha256(archive, pinned_sha256)?;
     }
-    let product = env::var("TFENV_PRODUCT")
-        .unwrap_or_else(|_| "terraform".to_string())
-        .to_lowercase();
-    let remote = env::var("TFENV_REMOTE").unwrap_or_else(|_| {
-        if product == "terraform" {
-            "https://releases.hashicorp.com/terraform/".to_string()
-        } else if product == "opentofu" {
-            "https://github.com/opentofu/opentofu/releases/download/".to_string()
-        } else {
-            // fallback: empty (user must set TFENV_REMOTE)
-            "".to_string()
+    let sums_path = match sums {
+        Some(p) => Some(p.to_path_buf()),
+        None => {
+            let dir = archive.parent().unwrap_or_else(|| Path::new("."));
+            product
+                .sha256sums_file(&version)
+                .or_else(|| {
+                    let url = product.asset_sha256sums_url(&version, file_name)?;
+                    url.rsplit('/').next().map(str::to_string)
+                })
+                .map(|file| dir.join(file))
+                .filter(|candidate| candidate.exists())
         }
-    });
-
-    let asset = asset_name(&product, &version);
-    let url = asset_url(&product, &remote, &version, &asset);
-    println!("Downloading {}", url);
-    let tmp = fetch_to_temp(&url)?;
-    println!("Downloaded to {}", tmp.path().display());
-    // For HashiCorp terraform releases we will verify SHA256SUMS where possible.
-    if product == "terraform" {
-        let sums = fetch_sha256sums(&remote, &version)?;
-        // find line matching asset
-        let mut expected: Option<String> = None;
-        for line in sums.lines() {
-            if line.contains(&asset) {
-                // format: <sha256>  <filename>
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 2 {
-                    expected = Some(parts[0].to_string());
-                    break;
-                }
+    };
+    match sums_path {
+        Some(sums_path) => {
+            let body = fs::read_to_string(&sums_path)
+                .with_context(|| format!("failed to read {}", sums_path.display()))?;
+            verify_checksum(&body, &sums_path.display().to_string(), file_name, archive)?;
+            println!("Checksum verified against {}", sums_path.display());
+            let mut sig_path = sums_path.clone().into_os_string();
+            sig_path.push(".sig");
+            let sig_path = PathBuf::from(sig_path);
+            if sig_path.exists() {
+                println!("Verifying {} with gpg", sig_path.display());
+                verify_sig_with_gpg(tfenv_root, &sig_path, &sums_path)?;
+                println!("GPG verification succeeded");
             }
         }
-        if expected.is_none() {
-            anyhow::bail!("No checksum found for asset {} in SHA256SUMS", asset);
-        }
-        let expected = expected.unwrap();
+        None if !pinned_sha256.is_empty() => {}
+        None => println!(
+            "No SHA256SUMS found for {}; skipping checksum verification.",
+            file_name
+        ),
+    }
 
-        let actual = compute_sha256(tmp.path())?;
-        if actual != expected {
-            anyhow::bail!("SHA256 mismatch: expected {} got {}", expected, actual);
+    let source = fs::canonicalize(archive).unwrap_or_else(|_| archive.to_path_buf());
+    let versions_dir = product.versions_dir(config_dir);
+    fs::create_dir_all(&versions_dir)?;
+    // a local file may have been renamed or repackaged: trust its extension, else its content
+    let format = match ArchiveFormat::from_file_name(file_name) {
+        Some(format) => format,
+        None => ArchiveFormat::sniff(archive)?,
+    };
+    let _lock = InstallLock::acquire(&versions_dir, &version)?;
+    install_archive(
+        config_dir,
+        &product,
+        &version,
+        archive,
+        format,
+        &source.display().to_string(),
+    )
+}
+
+/// `<os>_<arch>` pairs published for `version`, read from the release SHA256SUMS.
+fn available_builds(product: &Product, version: &str) -> Result<Vec<String>> {
+    let sums_url = product
+        .sha256sums_url(version)
+        .ok_or_else(|| anyhow::anyhow!("{} releases do not publish SHA256SUMS", product.name()))?;
+    let sums = parse_sha256sums(&fetch_sha256sums(&sums_url)?)?;
+    let mut builds: Vec<String> = sums
+        .keys()
+        .filter_map(|name| product.asset_platform(version, name))
+        .collect();
+    builds.sort();
+    Ok(builds)
+}
+
+/// Download the `os`/`arch` archive of `version` to a tempfile and verify it against pinned
+/// checksums and, where published, the release SHA256SUMS (and its gpg
+/// signature when enabled). Returns the tempfile and the URL it came from.
+fn download_verified(
+    tfenv_root: &Path,
+    product: &Product,
+    version: &str,
+    os: &str,
+    arch: &str,
+    pinned_sha256: &[String],
+) -> Result<(NamedTempFile, String)> {
+    let asset = &product.asset_name(version, os, arch);
+    let url = product.asset_url(version, asset);
+    println!("Downloading {}", url);
+    let tmp = match fetch_to_temp(&url) {
+        Ok(tmp) => tmp,
+        Err(e) => {
+            let platform = format!("{}_{}", os, arch);
+            return Err(match available_builds(product, version) {
+                Ok(builds) if !builds.is_empty() && !builds.contains(&platform) => {
+                    e.context(format!(
+                        "{} {} has no {} build; available builds: {}",
+                        product.name(),
+                        version,
+                        platform,
+                        builds.join(", ")
+                    ))
+                }
+                _ => e,
+            });
         }
+    };
+    println!("Downloaded to {}", tmp.path().display());
+    // Pinned checksums protect against a mirror serving a bad archive with a matching SUMS file
+    if !pinned_sha256.is_empty() {
+        verify_pinned_sha256(tmp.path(), pinned_sha256)?;
+    }
+    // Verify against the release SHA256SUMS where the product publishes one
+    if let Some(sums_url) = product.asset_sha256sums_url(version, asset) {
+        let sums = fetch_sha256sums(&sums_url)?;
+        verify_checksum(&sums, &sums_url, asset, tmp.path())?;
         println!("Checksum verified");
 
         // Optional PGP verification: if TFENV_TRUST_TFENV is set or use-gpgv file exists in TFENV_ROOT
         let trust = env::var("TFENV_TRUST_TFENV").unwrap_or_else(|_| "".to_string());
         let use_gpgv_file = tfenv_root.join("use-gpgv");
-        if trust == "yes" || use_gpgv_file.exists() {
+        if product.signature_scheme() == SignatureScheme::GpgDetached
+            && (trust == "yes" || use_gpgv_file.exists())
+        {
             println!("Verifying SHA256SUMS signature with gpg");
             // fetch sig and verify against sums
-            let sig_tmp = fetch_sig(&remote, &version)?;
+            let sig_tmp = fetch_sig(&format!("{}.sig", sums_url))?;
             // write sums to temp file
             let mut sums_tmp =
                 NamedTempFile::new().context("failed to create tempfile for sums")?;
@@ -256,20 +765,96 @@ pub fn install_version(
             verify_sig_with_gpg(tfenv_root, sig_tmp.path(), sums_tmp.path())?;
             println!("GPG verification succeeded");
         }
-    } else {
+    } else if pinned_sha256.is_empty() {
         println!(
             "Skipping checksum/PGP verification for product '{}' by default.",
-            product
+            product.name()
         );
     }
+    Ok((tmp, url))
+}
 
-    let versions_dir = config_dir.join("versions");
+pub fn install_version(
+    tfenv_root: &Path,
+    config_dir: &Path,
+    product: &Product,
+    requested: Option<&str>,
+    pinned_sha256: &[String],
+) -> Result<InstallStatus> {
+    let version = if let Some(v) = requested {
+        v.to_string()
+    } else {
+        "latest".to_string()
+    };
+    // If requested is "latest", resolve remote latest - for MVP we'll treat "latest" as error
+    if version == "latest" {
+        anyhow::bail!("'latest' resolution not implemented in installer; pass an explicit version");
+    }
+    let versions_dir = product.versions_dir(config_dir);
     fs::create_dir_all(&versions_dir)?;
-    extract_zip_to_version(tmp.path(), &versions_dir, &version)?;
-    println!(
-        "Installed terraform {} to {}",
-        version,
-        versions_dir.join(&version).display()
-    );
-    Ok(())
+    let _lock = InstallLock::acquire(&versions_dir, &version)?;
+    if versions_dir
+        .join(&version)
+        .join(product.binary_name())
+        .exists()
+    {
+        if !pinned_sha256.is_empty() {
+            verify_installed_sha256(config_dir, product, &version, pinned_sha256)?;
+        }
+        println!("{} {} is already installed", product.name(), version);
+        return Ok(InstallStatus::AlreadyInstalled);
+    }
+
+    let (tmp, url) = download_verified(
+        tfenv_root,
+        product,
+        &version,
+        map_os(),
+        map_arch(),
+        pinned_sha256,
+    )?;
+    install_archive(
+        config_dir,
+        product,
+        &version,
+        tmp.path(),
+        product.archive_format(),
+        &url,
+    )?;
+    Ok(InstallStatus::Installed)
+}
+
+/// Download and verify the release archive of `version` for another platform
+/// (`os`/`arch` as release or Rust names, e.g. `linux`/`arm64`) into `dest`, without
+/// installing it. Returns the path of the saved archive.
+pub fn download_version(
+    tfenv_root: &Path,
+    product: &Product,
+    version: &str,
+    os: &str,
+    arch: &str,
+    dest: &Path,
+    pinned_sha256: &[String],
+) -> Result<PathBuf> {
+    let os = os_name(os).ok_or_else(|| {
+        anyhow::anyhow!(
+            "unsupported OS '{}'; expected one of: {}",
+            os,
+            supported_names(OS_NAMES)
+        )
+    })?;
+    let arch = arch_name(arch).ok_or_else(|| {
+        anyhow::anyhow!(
+            "unsupported architecture '{}'; expected one of: {}",
+            arch,
+            supported_names(ARCH_NAMES)
+        )
+    })?;
+    let asset = product.asset_name(version, os, arch);
+    let (tmp, _url) = download_verified(tfenv_root, product, version, os, arch, pinned_sha256)?;
+    fs::create_dir_all(dest).with_context(|| format!("failed to create {}", dest.display()))?;
+    let out = dest.join(&asset);
+    fs::copy(tmp.path(), &out).with_context(|| format!("failed to write {}", out.display()))?;
+    println!("Saved {}", out.display());
+    Ok(out)
 }
@@ -1,2 +1,13 @@
+pub mod archive;
+pub mod backend;
+pub mod constraint;
+pub mod custom;
 pub mod installer;
+pub mod product;
+pub mod required;
+pub mod search;
+pub mod state;
+pub mod tool_versions;
+pub mod trace;
+pub mod version;
 pub use crate::installer::*;
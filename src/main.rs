@@ -4,17 +4,36 @@ use std::env;
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::process::Command;
-mod installer;
-use installer::install_version;
-use installer::terraform_binary_name;
-mod version;
-use version::resolve_version_name;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use tfenv_rs::custom::register_custom_products;
+use tfenv_rs::installer::download_version;
+use tfenv_rs::installer::install_from_archive;
+use tfenv_rs::installer::install_version;
+use tfenv_rs::installer::normalize_sha256;
+use tfenv_rs::installer::verify_installed_sha256;
+use tfenv_rs::installer::InstallStatus;
+use tfenv_rs::installer::{map_arch, map_os, validate_env_overrides};
+use tfenv_rs::product::{select_product, Product, ProductReason, ProductSelection};
+use tfenv_rs::state::check_state_version;
+use tfenv_rs::tool_versions::{write_tool_versions, TOOL_VERSIONS};
+use tfenv_rs::trace::Trace;
+use tfenv_rs::version;
+use tfenv_rs::version::{
+    collect_detected_tree_pins, collect_tree_pins, resolve_install_spec, resolve_version,
+    resolve_version_in, resolve_version_name, resolve_version_traced, working_dir,
+};
 
 #[derive(Parser)]
 #[command(name = "tfenv")]
 #[command(version)]
 #[command(about = "Terraform version manager (rust port)", long_about = None)]
 struct Cli {
+    /// Product to manage: terraform, opentofu or any releases.hashicorp.com product
+    /// (default: $TFENV_PRODUCT, else terraform)
+    #[arg(long, global = true)]
+    product: Option<String>,
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -22,46 +41,230 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Run a command using the selected Terraform version
-    Exec { args: Vec<String> },
+    Exec {
+        /// Arguments passed to the binary; a leading `-chdir=<dir>` also selects the
+        /// directory the version is resolved for
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
+    /// Run terragrunt with the Terragrunt and Terraform versions selected for this directory
+    Terragrunt {
+        /// Arguments passed to terragrunt
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        args: Vec<String>,
+    },
     /// Print resolved version
-    Version,
+    Version {
+        /// Print each step of the resolution: variables and files considered, the
+        /// spec found, how it was expanded and the candidates compared
+        #[arg(long)]
+        explain: bool,
+        /// Print the explanation as JSON
+        #[arg(long, requires = "explain")]
+        json: bool,
+    },
+    /// Print the selected product and what selected it
+    Product,
     /// Use/set a version (writes version file)
-    Use { version: String },
-    /// Install a version (not fully implemented)
-    Install { version: Option<String> },
+    Use {
+        version: String,
+        /// Pin the version in `./.tool-versions` (asdf/mise) instead of setting the default
+        #[arg(long)]
+        tool_versions: bool,
+    },
+    /// Install one or more versions (explicit, `latest`, `latest:<regex>`, ...)
+    Install {
+        versions: Vec<String>,
+        /// Number of versions to download and verify concurrently
+        #[arg(short, long, default_value_t = 4)]
+        jobs: usize,
+        /// Install every version pinned under DIR (version files and required_version)
+        #[arg(
+            short,
+            long,
+            value_name = "DIR",
+            num_args = 0..=1,
+            default_missing_value = ".",
+            conflicts_with_all = ["versions", "from_archive"]
+        )]
+        recursive: Option<PathBuf>,
+        /// Install from a release archive on disk instead of downloading it
+        #[arg(long, value_name = "ZIP")]
+        from_archive: Option<PathBuf>,
+        /// SHA256SUMS file to verify the archive against (default: next to the archive)
+        #[arg(long, value_name = "FILE", requires = "from_archive")]
+        sha256sums: Option<PathBuf>,
+        /// Expected SHA-256 of the archive; may be repeated (one per platform)
+        #[arg(long, value_name = "HEX")]
+        sha256: Vec<String>,
+    },
+    /// Download and verify a release archive for any platform without installing it
+    Download {
+        version: String,
+        /// Target OS in release naming (default: this host)
+        #[arg(long)]
+        os: Option<String>,
+        /// Target architecture in release naming (default: this host)
+        #[arg(long)]
+        arch: Option<String>,
+        /// Directory to save the archive in
+        #[arg(long, value_name = "DIR", default_value = ".")]
+        dest: PathBuf,
+        /// Expected SHA-256 of the archive; may be repeated
+        #[arg(long, value_name = "HEX")]
+        sha256: Vec<String>,
+    },
     /// List installed versions
     List,
-    /// List remote versions (optional: filter by 'terraform' or 'opentofu')
+    /// List remote versions (optional: product to list, e.g. 'opentofu' or 'packer')
     ListRemote { product: Option<String> },
 }
 
 fn main() -> Result<()> {
     let cli = Cli::parse();
+    validate_env_overrides()?;
     let tfenv_root = detect_tfenv_root()?;
     let config_dir = env::var("TFENV_CONFIG_DIR")
         .map(PathBuf::from)
         .unwrap_or_else(|_| tfenv_root.clone());
+    register_custom_products(&config_dir)?;
+    let work_dir = working_dir()?;
+    let selection = select_product(cli.product.as_deref(), &work_dir)?;
+    let product = selection.product.clone();
     if let Some(cmd) = cli.command {
         match cmd {
-            Commands::Exec { args } => run_exec(&tfenv_root, &config_dir, &args),
+            Commands::Exec { args } => {
+                // like terraform itself, resolve for the directory given by -chdir; the
+                // binary runs in the process's directory, so -chdir is relative to it
+                // rather than to TFENV_DIR
+                let (dir, product) = match version::chdir_arg(&args) {
+                    Some(chdir) => {
+                        let dir = version::join_dir(&env::current_dir()?, chdir);
+                        let product = select_product(cli.product.as_deref(), &dir)?.product;
+                        (dir, product)
+                    }
+                    None => (work_dir, product),
+                };
+                run_exec(&tfenv_root, &config_dir, &product, &dir, &args)
+            }
+            Commands::Terragrunt { args } => {
+                run_terragrunt(&tfenv_root, &config_dir, &product, &work_dir, &args)
+            }
             // `version` prints the resolved terraform/version selection (like tfenv use/resolution)
-            Commands::Version => {
-                let v = resolve_version_name(&tfenv_root, &config_dir)?;
+            Commands::Version {
+                explain: true,
+                json,
+            } => {
+                let mut trace = Trace::default();
+                trace.note(
+                    "product",
+                    format!("{} (selected by {})", product.name(), selection.reason),
+                );
+                if let Ok(dir) = env::var("TFENV_DIR") {
+                    trace.note("dir", format!("TFENV_DIR={}", dir));
+                }
+                explain_version(&tfenv_root, &config_dir, &product, &work_dir, trace, json)
+            }
+            Commands::Version { .. } => {
+                let v = resolve_version_name(&tfenv_root, &config_dir, &product)?;
                 println!("{}", v);
                 Ok(())
             }
-            Commands::Use { version } => set_default_version(&config_dir, &version),
-            Commands::Install { version } => {
+            Commands::Product => {
+                println!("{} (selected by {})", product.name(), selection.reason);
+                Ok(())
+            }
+            Commands::Use {
+                version,
+                tool_versions: true,
+            } => {
+                let path = work_dir.join(TOOL_VERSIONS);
+                write_tool_versions(&path, &product, &version)?;
+                println!(
+                    "Pinned {} {} in {}",
+                    product.name(),
+                    version,
+                    path.display()
+                );
+                Ok(())
+            }
+            Commands::Use { version, .. } => set_default_version(&config_dir, &product, &version),
+            Commands::Install {
+                versions,
+                jobs,
+                recursive,
+                from_archive,
+                sha256sums,
+                sha256,
+            } => {
+                let mut pinned = sha256
+                    .iter()
+                    .map(|h| normalize_sha256(h))
+                    .collect::<Result<Vec<_>>>()?;
+                if versions.len() > 1 && (!pinned.is_empty() || from_archive.is_some()) {
+                    anyhow::bail!("--sha256 and --from-archive take a single version");
+                }
+                if let Some(root) = recursive {
+                    return install_recursive(&tfenv_root, &config_dir, &selection, &root, jobs);
+                }
+                if let Some(archive) = from_archive {
+                    return install_from_archive(
+                        &tfenv_root,
+                        &config_dir,
+                        &archive,
+                        cli.product.as_deref(),
+                        versions.first().map(|v| v.as_str()),
+                        sha256sums.as_deref(),
+                        &pinned,
+                    );
+                }
                 // If no version supplied, resolve via the same rules as `use`/`exec`
-                if let Some(v) = version {
-                    install_version(&tfenv_root, &config_dir, Some(&v))
+                if versions.is_empty() {
+                    let resolved = resolve_version(&tfenv_root, &config_dir, &product)?;
+                    pinned.extend(resolved.sha256);
+                    install_version(
+                        &tfenv_root,
+                        &config_dir,
+                        &product,
+                        Some(&resolved.version),
+                        &pinned,
+                    )?;
+                    Ok(())
                 } else {
-                    let resolved = resolve_version_name(&tfenv_root, &config_dir)?;
-                    install_version(&tfenv_root, &config_dir, Some(&resolved))
+                    install_specs(&tfenv_root, &config_dir, &product, &versions, jobs, &pinned)
                 }
             }
-            Commands::List => list_installed(&config_dir),
-            Commands::ListRemote { product } => list_remote(product),
+            Commands::Download {
+                version,
+                os,
+                arch,
+                dest,
+                sha256,
+            } => {
+                let pinned = sha256
+                    .iter()
+                    .map(|h| normalize_sha256(h))
+                    .collect::<Result<Vec<_>>>()?;
+                let version =
+                    resolve_install_spec(&version, &work_dir, &tfenv_root, &config_dir, &product)?;
+                download_version(
+                    &tfenv_root,
+                    &product,
+                    &version,
+                    os.as_deref().unwrap_or_else(|| map_os()),
+                    arch.as_deref().unwrap_or_else(|| map_arch()),
+                    &dest,
+                    &pinned,
+                )?;
+                Ok(())
+            }
+            Commands::List => list_installed(&config_dir, &product),
+            Commands::ListRemote {
+                product: listed_product,
+            } => match listed_product {
+                Some(name) => list_remote(&Product::from_name(&name)?),
+                None => list_remote(&product),
+            },
         }
     } else {
         // No command: print program version and help (similar to original tfenv behavior)
@@ -73,6 +276,25 @@ fn main() -> Result<()> {
     }
 }
 
+/// Print the resolution trace (also when the resolution fails, then returning its error).
+fn explain_version(
+    tfenv_root: &Path,
+    config_dir: &Path,
+    product: &Product,
+    dir: &Path,
+    mut trace: Trace,
+    json: bool,
+) -> Result<()> {
+    let outcome = resolve_version_traced(tfenv_root, config_dir, product, dir, &mut trace)
+        .map(|resolved| resolved.version);
+    if json {
+        println!("{}", trace.to_json(&outcome)?);
+    } else {
+        print!("{}", trace);
+    }
+    outcome.map(|_| ())
+}
+
 fn detect_tfenv_root() -> Result<PathBuf> {
     if let Ok(root) = env::var("TFENV_ROOT") {
         return Ok(PathBuf::from(root));
@@ -87,46 +309,255 @@ fn detect_tfenv_root() -> Result<PathBuf> {
     Err(anyhow::anyhow!("Unable to determine TFENV_ROOT"))
 }
 
-
-
-fn run_exec(tfenv_root: &Path, config_dir: &Path, args: &[String]) -> Result<()> {
-    let version = resolve_version_name(tfenv_root, config_dir)?;
-    let tf_path = config_dir
-        .join("versions")
+/// The version of `product` selected for the working directory and the path of
+/// its binary, installing it first when `TFENV_AUTO_INSTALL` allows.
+fn selected_binary(
+    tfenv_root: &Path,
+    config_dir: &Path,
+    product: &Product,
+    dir: &Path,
+) -> Result<(String, PathBuf)> {
+    let resolved = resolve_version_in(tfenv_root, config_dir, product, dir)?;
+    let version = resolved.version;
+    let tf_path = product
+        .versions_dir(config_dir)
         .join(&version)
-        .join(terraform_binary_name());
-    if !tf_path.exists() {
+        .join(product.binary_name());
+    if tf_path.exists() {
+        // pinned checksums hold for versions installed earlier too
+        if !resolved.sha256.is_empty() {
+            verify_installed_sha256(config_dir, product, &version, &resolved.sha256)?;
+        }
+    } else {
         // Auto-install if TFENV_AUTO_INSTALL is true (default true)
         let auto = env::var("TFENV_AUTO_INSTALL").unwrap_or_else(|_| "true".to_string());
         if auto == "true" {
             println!("Version {} not installed; auto-installing...", version);
-            install_version(tfenv_root, config_dir, Some(&version))?;
+            install_version(
+                tfenv_root,
+                config_dir,
+                product,
+                Some(&version),
+                &resolved.sha256,
+            )?;
         } else {
             anyhow::bail!(
-                "Terraform binary for version '{}' not installed at {}",
+                "{} binary for version '{}' not installed at {}",
+                product.name(),
                 version,
                 tf_path.display()
             );
         }
     }
+    Ok((version, tf_path))
+}
 
+fn run_exec(
+    tfenv_root: &Path,
+    config_dir: &Path,
+    product: &Product,
+    dir: &Path,
+    args: &[String],
+) -> Result<()> {
+    let (version, tf_path) = selected_binary(tfenv_root, config_dir, product, dir)?;
+    // Terraform and OpenTofu refuse state written by a newer release
+    if product.reads_terraform_state() {
+        check_state_version(dir, &version)?;
+    }
     let mut cmd = Command::new(tf_path);
     if !args.is_empty() {
         cmd.args(args);
     }
-    let status = cmd.status().context("failed to execute terraform")?;
+    let status = cmd
+        .status()
+        .with_context(|| format!("failed to execute {}", product.binary()))?;
     std::process::exit(status.code().unwrap_or(1));
 }
 
-fn set_default_version(config_dir: &Path, version: &str) -> Result<()> {
-    let path = config_dir.join("version");
+/// Run the selected Terragrunt and point it at the Terraform (or, with
+/// `--product opentofu`, OpenTofu) selected for the same directory.
+fn run_terragrunt(
+    tfenv_root: &Path,
+    config_dir: &Path,
+    product: &Product,
+    dir: &Path,
+    args: &[String],
+) -> Result<()> {
+    let terragrunt = Product::from_name("terragrunt")?;
+    let engine = if product.name() == terragrunt.name() {
+        Product::from_name("terraform")?
+    } else {
+        product.clone()
+    };
+    let (_, tg_path) = selected_binary(tfenv_root, config_dir, &terragrunt, dir)?;
+    let (_, tf_path) = selected_binary(tfenv_root, config_dir, &engine, dir)?;
+    let status = Command::new(tg_path)
+        .args(args)
+        // TG_TF_PATH replaces TERRAGRUNT_TFPATH in newer Terragrunt releases
+        .env("TERRAGRUNT_TFPATH", &tf_path)
+        .env("TG_TF_PATH", &tf_path)
+        .status()
+        .context("failed to execute terragrunt")?;
+    std::process::exit(status.code().unwrap_or(1));
+}
+
+/// Resolved version and what the installer did with it, per requested spec.
+type InstallOutcome = Result<(String, InstallStatus)>;
+
+/// Resolve and install each spec, with up to `jobs` installs in flight. Duplicate
+/// specs resolving to one version are serialised by the installer's per-version lock.
+fn install_specs(
+    tfenv_root: &Path,
+    config_dir: &Path,
+    product: &Product,
+    specs: &[String],
+    jobs: usize,
+    pinned: &[String],
+) -> Result<()> {
+    let install_one = |spec: &str| -> InstallOutcome {
+        let version = resolve_install_spec(spec, &working_dir()?, tfenv_root, config_dir, product)?;
+        let status = install_version(tfenv_root, config_dir, product, Some(&version), pinned)?;
+        Ok((version, status))
+    };
+    if let [spec] = specs {
+        install_one(spec)?;
+        return Ok(());
+    }
+
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<InstallOutcome>>> =
+        Mutex::new(specs.iter().map(|_| None).collect());
+    thread::scope(|scope| {
+        for _ in 0..jobs.clamp(1, specs.len()) {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                let Some(spec) = specs.get(i) else {
+                    break;
+                };
+                let outcome = install_one(spec);
+                results.lock().unwrap()[i] = Some(outcome);
+            });
+        }
+    });
+
+    println!();
+    let mut failed = 0;
+    for (spec, outcome) in specs.iter().zip(results.into_inner().unwrap()) {
+        match outcome {
+            Some(Ok((version, InstallStatus::Installed))) => {
+                println!("{:<20} {} installed", spec, version)
+            }
+            Some(Ok((version, InstallStatus::AlreadyInstalled))) => {
+                println!("{:<20} {} already installed", spec, version)
+            }
+            Some(Err(e)) => {
+                failed += 1;
+                println!("{:<20} FAILED: {:#}", spec, e);
+            }
+            None => unreachable!("every spec is processed by a worker"),
+        }
+    }
+    if failed > 0 {
+        anyhow::bail!("{} of {} installs failed", failed, specs.len());
+    }
+    Ok(())
+}
+
+/// Resolve every version pinned below `root` in its own directory, dedupe the
+/// results and install them in parallel. Unless a product was chosen explicitly,
+/// each directory is installed for the product detected there, so that mixed
+/// Terraform/OpenTofu trees get both.
+fn install_recursive(
+    tfenv_root: &Path,
+    config_dir: &Path,
+    selection: &ProductSelection,
+    root: &Path,
+    jobs: usize,
+) -> Result<()> {
+    let detected = matches!(
+        selection.reason,
+        ProductReason::Detected { .. } | ProductReason::Default
+    );
+    let mut products = Vec::new();
+    if detected {
+        for name in ["terraform", "opentofu"] {
+            let product = Product::from_name(name)?;
+            let pins = collect_detected_tree_pins(root, &product)?;
+            products.push((product, pins));
+        }
+    } else {
+        let pins = collect_tree_pins(root, &selection.product)?;
+        products.push((selection.product.clone(), pins));
+    }
+    let total: usize = products.iter().map(|(_, pins)| pins.len()).sum();
+    if total == 0 {
+        println!(
+            "No version files or required_version constraints under {}",
+            root.display()
+        );
+        return Ok(());
+    }
+    let mut unresolved = 0;
+    let mut failed = None;
+    for (product, pins) in &products {
+        let mut versions: Vec<String> = Vec::new();
+        for pin in pins {
+            match resolve_install_spec(&pin.spec, &pin.dir, tfenv_root, config_dir, product) {
+                Ok(version) => {
+                    if detected {
+                        println!(
+                            "{}: {} -> {} {}",
+                            pin.source.display(),
+                            pin.spec,
+                            product.name(),
+                            version
+                        );
+                    } else {
+                        println!("{}: {} -> {}", pin.source.display(), pin.spec, version);
+                    }
+                    if !versions.contains(&version) {
+                        versions.push(version);
+                    }
+                }
+                Err(e) => {
+                    unresolved += 1;
+                    eprintln!(
+                        "{}: cannot resolve '{}': {:#}",
+                        pin.source.display(),
+                        pin.spec,
+                        e
+                    );
+                }
+            }
+        }
+        // a failed install of one product should not keep the other's from running
+        if !versions.is_empty() {
+            if let Err(e) = install_specs(tfenv_root, config_dir, product, &versions, jobs, &[]) {
+                failed.get_or_insert(e);
+            }
+        }
+    }
+    if let Some(e) = failed {
+        return Err(e);
+    }
+    if unresolved > 0 {
+        anyhow::bail!("{} of {} pins could not be resolved", unresolved, total);
+    }
+    Ok(())
+}
+
+fn set_default_version(config_dir: &Path, product: &Product, version: &str) -> Result<()> {
+    let path = product.default_version_path(config_dir);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
     fs::write(&path, version).context("failed to write version file")?;
-    println!("Set default version to {}", version);
+    println!("Set default {} version to {}", product.name(), version);
     Ok(())
 }
 
-fn list_installed(config_dir: &Path) -> Result<()> {
-    let versions_dir = config_dir.join("versions");
+fn list_installed(config_dir: &Path, product: &Product) -> Result<()> {
+    let versions_dir = product.versions_dir(config_dir);
     if !versions_dir.exists() {
         println!("(no versions installed)");
         return Ok(());
@@ -135,6 +566,10 @@ fn list_installed(config_dir: &Path) -> Result<()> {
         let e = entry?;
         if e.path().is_dir() {
             if let Some(name) = e.file_name().to_str() {
+                // skip staging directories left by in-progress installs
+                if name.starts_with('.') {
+                    continue;
+                }
                 println!("{}", name);
             }
         }
@@ -142,14 +577,8 @@ fn list_installed(config_dir: &Path) -> Result<()> {
     Ok(())
 }
 
-fn list_remote(product_filter: Option<String>) -> Result<()> {
-    let versions = version::list_remote_versions()?;
-    for (v, product) in versions {
-        if let Some(ref filter) = product_filter {
-            if product.to_lowercase() != filter.to_lowercase() {
-                continue;
-            }
-        }
+fn list_remote(product: &Product) -> Result<()> {
+    for (v, product) in version::list_remote_versions(product)? {
         println!("{} {}", v, product);
     }
     Ok(())
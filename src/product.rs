@@ -0,0 +1,232 @@
+use crate::backend::{HashiCorp, OpenTofu, ProductBackend, Terragrunt};
+use crate::search::search_dirs;
+use crate::tool_versions::{parse_tool_versions, ToolVersionsMode, TOOL_VERSIONS};
+use anyhow::Result;
+use regex::Regex;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
+
+static REGISTRY: OnceLock<RwLock<Vec<Arc<dyn ProductBackend>>>> = OnceLock::new();
+
+/// Make `backend` available to `Product::from_name` under its name. Registered
+/// backends take precedence over the built-in ones, so a patched fork of a product
+/// can replace it; a later registration of the same name replaces an earlier one.
+pub fn register_backend(backend: impl ProductBackend + 'static) {
+    let registry = REGISTRY.get_or_init(|| RwLock::new(Vec::new()));
+    let mut backends = registry.write().unwrap();
+    backends.retain(|b| b.name() != backend.name());
+    backends.push(Arc::new(backend));
+}
+
+fn registered(name: &str) -> Option<Arc<dyn ProductBackend>> {
+    let backends = REGISTRY.get()?.read().unwrap();
+    backends.iter().find(|b| b.name() == name).cloned()
+}
+
+/// A product tfenv can manage: its release backend plus where its versions are
+/// installed and pinned locally.
+#[derive(Clone)]
+pub struct Product {
+    backend: Arc<dyn ProductBackend>,
+}
+
+impl Product {
+    /// Look up a product by name: registered backends first, then Terraform,
+    /// OpenTofu and Terragrunt; any other name is treated as a product on
+    /// releases.hashicorp.com (packer, vault, consul, terraform-ls, sentinel, ...).
+    pub fn from_name(name: &str) -> Result<Self> {
+        let name = name.trim().to_lowercase();
+        if let Some(backend) = registered(&name) {
+            return Ok(Self { backend });
+        }
+        let backend: Arc<dyn ProductBackend> = match name.as_str() {
+            "opentofu" | "tofu" => Arc::new(OpenTofu),
+            "terragrunt" => Arc::new(Terragrunt),
+            _ => {
+                if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+                    anyhow::bail!("invalid product name '{}'", name);
+                }
+                Arc::new(HashiCorp::new(&name))
+            }
+        };
+        Ok(Self { backend })
+    }
+
+    /// Wrap a backend without registering it by name.
+    pub fn from_backend(backend: impl ProductBackend + 'static) -> Self {
+        Self {
+            backend: Arc::new(backend),
+        }
+    }
+
+    /// The product for `dir`; see [`select_product`].
+    pub fn current(flag: Option<&str>, dir: &Path) -> Result<Self> {
+        Ok(select_product(flag, dir)?.product)
+    }
+
+    /// Executable file name on this platform.
+    pub fn binary_name(&self) -> String {
+        if cfg!(windows) {
+            format!("{}.exe", self.binary())
+        } else {
+            self.binary().to_string()
+        }
+    }
+
+    /// Directory holding installed versions. Terraform keeps the original tfenv
+    /// layout (`versions/`); other products get their own `<name>/versions/`.
+    pub fn versions_dir(&self, config_dir: &Path) -> PathBuf {
+        self.config_subdir(config_dir).join("versions")
+    }
+
+    /// Default version written by `tfenv use` (`version`, or `<name>/version`).
+    pub fn default_version_path(&self, config_dir: &Path) -> PathBuf {
+        self.config_subdir(config_dir).join("version")
+    }
+
+    fn config_subdir(&self, config_dir: &Path) -> PathBuf {
+        if self.name() == "terraform" {
+            config_dir.to_path_buf()
+        } else {
+            config_dir.join(self.name())
+        }
+    }
+}
+
+/// What selected a product.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProductReason {
+    /// The global `--product` flag
+    Flag,
+    /// `TFENV_PRODUCT`
+    Env,
+    /// A marker found by [`detect_product`]: the file and what it is
+    Detected { path: PathBuf, marker: &'static str },
+    /// Nothing selected a product
+    Default,
+}
+
+impl fmt::Display for ProductReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Flag => write!(f, "--product flag"),
+            Self::Env => write!(f, "TFENV_PRODUCT"),
+            Self::Detected { path, marker } => write!(f, "{} at {}", marker, path.display()),
+            Self::Default => write!(f, "default"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ProductSelection {
+    pub product: Product,
+    pub reason: ProductReason,
+}
+
+/// Select the product for `dir`: the `--product` flag, else `TFENV_PRODUCT`, else the
+/// product detected from the nearest marker in `dir` or its parents, else Terraform.
+pub fn select_product(flag: Option<&str>, dir: &Path) -> Result<ProductSelection> {
+    let (name, reason) = if let Some(name) = flag {
+        (name.to_string(), ProductReason::Flag)
+    } else if let Some(name) = env::var("TFENV_PRODUCT").ok().filter(|p| !p.is_empty()) {
+        (name, ProductReason::Env)
+    } else if let Some((name, path, marker)) = detect_product(dir) {
+        (name.to_string(), ProductReason::Detected { path, marker })
+    } else {
+        ("terraform".to_string(), ProductReason::Default)
+    };
+    Ok(ProductSelection {
+        product: Product::from_name(&name)?,
+        reason,
+    })
+}
+
+/// Product implied by the nearest directory from `start` upwards (within the search
+/// boundaries, see [`search_dirs`]) holding a marker, with the marker file and a
+/// description. Within one directory the markers are checked in this order:
+///
+/// 1. `.opentofu-version` selects OpenTofu
+/// 2. `*.tofu`/`*.tofu.json` files select OpenTofu
+/// 3. `.tf`/`.tf.json` files with providers from `registry.opentofu.org` select OpenTofu
+/// 4. `.terraform-version` selects Terraform
+/// 5. `.tool-versions` selects OpenTofu if it lists it, else Terraform if it lists it
+pub fn detect_product(start: &Path) -> Option<(&'static str, PathBuf, &'static str)> {
+    search_dirs(start)
+        .0
+        .iter()
+        .find_map(|dir| detect_in_dir(dir))
+}
+
+fn detect_in_dir(dir: &Path) -> Option<(&'static str, PathBuf, &'static str)> {
+    let marker = dir.join(".opentofu-version");
+    if marker.is_file() {
+        return Some(("opentofu", marker, ".opentofu-version"));
+    }
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .ok()?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .collect();
+    files.sort();
+    let name_of = |p: &Path| {
+        p.file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default()
+            .to_string()
+    };
+    if let Some(tofu) = files.iter().find(|p| {
+        let name = name_of(p);
+        name.ends_with(".tofu") || name.ends_with(".tofu.json")
+    }) {
+        return Some(("opentofu", tofu.clone(), "OpenTofu configuration file"));
+    }
+    let opentofu_source = Regex::new(r#"source"?\s*[:=]\s*"registry\.opentofu\.org/"#).unwrap();
+    for tf in files.iter().filter(|p| {
+        let name = name_of(p);
+        name.ends_with(".tf") || name.ends_with(".tf.json")
+    }) {
+        if fs::read_to_string(tf).is_ok_and(|body| opentofu_source.is_match(&body)) {
+            return Some((
+                "opentofu",
+                tf.clone(),
+                "provider from registry.opentofu.org",
+            ));
+        }
+    }
+    let marker = dir.join(".terraform-version");
+    if marker.is_file() {
+        return Some(("terraform", marker, ".terraform-version"));
+    }
+    let marker = dir.join(TOOL_VERSIONS);
+    if marker.is_file() && ToolVersionsMode::from_env().ok() != Some(ToolVersionsMode::Ignore) {
+        let body = fs::read_to_string(&marker).ok()?;
+        for (name, description) in [
+            ("opentofu", "opentofu in .tool-versions"),
+            ("terraform", "terraform in .tool-versions"),
+        ] {
+            if parse_tool_versions(&body, &Product::from_name(name).ok()?).is_some() {
+                return Some((name, marker, description));
+            }
+        }
+    }
+    None
+}
+
+impl Deref for Product {
+    type Target = dyn ProductBackend;
+
+    fn deref(&self) -> &Self::Target {
+        self.backend.as_ref()
+    }
+}
+
+impl fmt::Debug for Product {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Product").field(&self.name()).finish()
+    }
+}
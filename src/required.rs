@@ -0,0 +1,241 @@
+use crate::product::Product;
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A version constraint declared in a configuration file, e.g. `required_version`
+/// in a `terraform` block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequiredVersion {
+    pub spec: String,
+    pub file: PathBuf,
+}
+
+impl fmt::Display for RequiredVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{}' in {}", self.spec, self.file.display())
+    }
+}
+
+/// The product's configuration files in `dir`, sorted. Like OpenTofu, a `.tofu`
+/// (`.tofu.json`) file hides the `.tf` (`.tf.json`) file of the same name.
+pub fn configuration_files(dir: &Path, product: &Product) -> Vec<PathBuf> {
+    let names: HashSet<String> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .flatten()
+            .filter(|e| e.path().is_file())
+            .filter_map(|e| e.file_name().to_str().map(str::to_string))
+            .filter(|n| product.is_constraint_file(n))
+            .collect(),
+        Err(_) => return Vec::new(),
+    };
+    let hidden = |name: &str| {
+        let tofu = if let Some(stem) = name.strip_suffix(".tf.json") {
+            format!("{}.tofu.json", stem)
+        } else if let Some(stem) = name.strip_suffix(".tf") {
+            format!("{}.tofu", stem)
+        } else {
+            return false;
+        };
+        names.contains(&tofu)
+    };
+    let mut files: Vec<PathBuf> = names
+        .iter()
+        .filter(|n| !hidden(n))
+        .map(|n| dir.join(n))
+        .collect();
+    files.sort();
+    files
+}
+
+/// What a configuration file declares that matters for version resolution.
+#[derive(Debug, Default)]
+struct FileConfig {
+    specs: Vec<String>,
+    /// `source` of each `module` block
+    module_sources: Vec<String>,
+}
+
+fn parse_file(file: &Path, product: &Product) -> Result<FileConfig> {
+    let body =
+        fs::read_to_string(file).with_context(|| format!("failed to read {}", file.display()))?;
+    if file.extension().is_some_and(|e| e == "json") {
+        parse_json(&body, product)
+    } else {
+        parse_hcl(&body, product)
+    }
+    .with_context(|| format!("failed to parse {}", file.display()))
+}
+
+/// Every version constraint declared in the configuration files of `dir`: the
+/// product's `constraint_attribute` in each of its `constraint_block`s (or at the
+/// top level). `.json` files are read as JSON, everything else as HCL.
+pub fn required_versions(dir: &Path, product: &Product) -> Result<Vec<RequiredVersion>> {
+    let mut found = Vec::new();
+    for file in configuration_files(dir, product) {
+        let config = parse_file(&file, product)?;
+        found.extend(config.specs.into_iter().map(|spec| RequiredVersion {
+            spec,
+            file: file.clone(),
+        }));
+    }
+    Ok(found)
+}
+
+/// Every version constraint of the configuration rooted at `root`: those of `root`
+/// itself, of the local modules it calls (`source = "./modules/x"`, recursively)
+/// and of the modules installed by `terraform init` according to
+/// `.terraform/modules/modules.json`.
+pub fn configuration_required_versions(
+    root: &Path,
+    product: &Product,
+) -> Result<Vec<RequiredVersion>> {
+    let mut pending = vec![root.to_path_buf()];
+    pending.extend(installed_module_dirs(root)?);
+    let mut visited = HashSet::new();
+    let mut found = Vec::new();
+    while let Some(dir) = pending.pop() {
+        let Ok(canonical) = dir.canonicalize() else {
+            continue;
+        };
+        if !visited.insert(canonical) {
+            continue;
+        }
+        for file in configuration_files(&dir, product) {
+            let config = parse_file(&file, product)?;
+            found.extend(config.specs.into_iter().map(|spec| RequiredVersion {
+                spec,
+                file: file.clone(),
+            }));
+            pending.extend(
+                config
+                    .module_sources
+                    .iter()
+                    .filter(|s| is_local_source(s))
+                    .map(|s| dir.join(s)),
+            );
+        }
+    }
+    found.sort_by(|a, b| a.file.cmp(&b.file));
+    Ok(found)
+}
+
+/// Whether a module `source` is a local path rather than a registry or remote address.
+fn is_local_source(source: &str) -> bool {
+    source.starts_with("./") || source.starts_with("../")
+}
+
+/// Directories of the local modules (`source = "./modules/x"`) called by the
+/// configuration in `dir`, canonicalized.
+pub fn local_module_dirs(dir: &Path, product: &Product) -> Result<Vec<PathBuf>> {
+    let mut dirs = Vec::new();
+    for file in configuration_files(dir, product) {
+        let config = parse_file(&file, product)?;
+        dirs.extend(
+            config
+                .module_sources
+                .iter()
+                .filter(|s| is_local_source(s))
+                .filter_map(|s| dir.join(s).canonicalize().ok()),
+        );
+    }
+    Ok(dirs)
+}
+
+/// Module directories recorded in `<root>/.terraform/modules/modules.json`.
+fn installed_module_dirs(root: &Path) -> Result<Vec<PathBuf>> {
+    let manifest = root.join(".terraform").join("modules").join("modules.json");
+    if !manifest.is_file() {
+        return Ok(Vec::new());
+    }
+    let body = fs::read_to_string(&manifest)
+        .with_context(|| format!("failed to read {}", manifest.display()))?;
+    let json: Value = serde_json::from_str(&body)
+        .with_context(|| format!("failed to parse {}", manifest.display()))?;
+    Ok(json
+        .get("Modules")
+        .and_then(Value::as_array)
+        .map(|modules| {
+            modules
+                .iter()
+                .filter_map(|m| m.get("Dir").and_then(Value::as_str))
+                .map(|dir| root.join(dir))
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+/// Whether `dir` declares a constraint. Unparsable files count, so that resolving
+/// the constraint reports the parse error instead of silently looking elsewhere.
+pub fn has_required_version(dir: &Path, product: &Product) -> bool {
+    required_versions(dir, product).map_or(true, |found| !found.is_empty())
+}
+
+fn parse_hcl(body: &str, product: &Product) -> Result<FileConfig> {
+    let body = hcl::parse(body)?;
+    let attribute = product.constraint_attribute();
+    let bodies: Vec<&hcl::Body> = match product.constraint_block() {
+        Some(block) => body
+            .blocks()
+            .filter(|b| b.identifier() == block)
+            .map(|b| b.body())
+            .collect(),
+        None => vec![&body],
+    };
+    let mut config = FileConfig::default();
+    for block in bodies {
+        for attr in block.attributes().filter(|a| a.key() == attribute) {
+            match attr.expr() {
+                hcl::Expression::String(spec) => config.specs.push(spec.clone()),
+                _ => anyhow::bail!("{} must be a literal string", attribute),
+            }
+        }
+    }
+    for module in body.blocks().filter(|b| b.identifier() == "module") {
+        for attr in module.body().attributes().filter(|a| a.key() == "source") {
+            if let hcl::Expression::String(source) = attr.expr() {
+                config.module_sources.push(source.clone());
+            }
+        }
+    }
+    Ok(config)
+}
+
+/// A block in JSON configuration may be given as an object or an array of objects.
+fn json_objects(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Array(items) => items.iter().collect(),
+        other => vec![other],
+    }
+}
+
+fn parse_json(body: &str, product: &Product) -> Result<FileConfig> {
+    let json: Value = serde_json::from_str(body)?;
+    let attribute = product.constraint_attribute();
+    let bodies = match product.constraint_block() {
+        Some(block) => json.get(block).map(json_objects).unwrap_or_default(),
+        None => vec![&json],
+    };
+    let mut config = FileConfig::default();
+    for block in bodies {
+        match block.get(attribute) {
+            Some(Value::String(spec)) => config.specs.push(spec.clone()),
+            Some(_) => anyhow::bail!("{} must be a string", attribute),
+            None => {}
+        }
+    }
+    // "module": { "<name>": { "source": ... } }
+    for modules in json.get("module").map(json_objects).unwrap_or_default() {
+        for module in modules.as_object().into_iter().flat_map(|m| m.values()) {
+            for module in json_objects(module) {
+                if let Some(source) = module.get("source").and_then(Value::as_str) {
+                    config.module_sources.push(source.to_string());
+                }
+            }
+        }
+    }
+    Ok(config)
+}
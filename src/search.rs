@@ -0,0 +1,74 @@
+use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Where an upward search for version files or markers stopped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchStop {
+    /// The filesystem root was reached
+    Root(PathBuf),
+    /// The next directory up is listed in `TFENV_CEILING_DIRECTORIES`
+    Ceiling(PathBuf),
+    /// The enclosing git repository's root (with `TFENV_STOP_AT_GIT_ROOT=true`)
+    GitRoot(PathBuf),
+}
+
+impl fmt::Display for SearchStop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Root(dir) => write!(f, "reached {}", dir.display()),
+            Self::Ceiling(dir) => write!(
+                f,
+                "stopped below {} (TFENV_CEILING_DIRECTORIES)",
+                dir.display()
+            ),
+            Self::GitRoot(dir) => write!(
+                f,
+                "stopped at git root {} (TFENV_STOP_AT_GIT_ROOT)",
+                dir.display()
+            ),
+        }
+    }
+}
+
+/// Absolute directories from `TFENV_CEILING_DIRECTORIES` (separated like `PATH`).
+/// As with git's `GIT_CEILING_DIRECTORIES`, searches never enter them, but may
+/// start in one.
+fn ceiling_directories() -> Vec<PathBuf> {
+    let Some(list) = env::var_os("TFENV_CEILING_DIRECTORIES") else {
+        return Vec::new();
+    };
+    env::split_paths(&list)
+        .filter(|p| p.is_absolute())
+        .flat_map(|p| {
+            let canonical = p.canonicalize().ok();
+            std::iter::once(p).chain(canonical)
+        })
+        .collect()
+}
+
+fn stop_at_git_root() -> bool {
+    env::var("TFENV_STOP_AT_GIT_ROOT").is_ok_and(|v| v == "true")
+}
+
+/// `start` and the directories above it that an upward search may look in, and
+/// why the search ends where it does.
+pub fn search_dirs(start: &Path) -> (Vec<PathBuf>, SearchStop) {
+    let ceilings = ceiling_directories();
+    let git = stop_at_git_root();
+    let mut dirs = Vec::new();
+    let mut dir = start.to_path_buf();
+    loop {
+        dirs.push(dir.clone());
+        if git && dir.join(".git").exists() {
+            return (dirs, SearchStop::GitRoot(dir));
+        }
+        let Some(parent) = dir.parent().map(Path::to_path_buf) else {
+            return (dirs, SearchStop::Root(dir));
+        };
+        if ceilings.contains(&parent) {
+            return (dirs, SearchStop::Ceiling(parent));
+        }
+        dir = parent;
+    }
+}
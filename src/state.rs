@@ -0,0 +1,124 @@
+use crate::version::current_workspace;
+use anyhow::{Context, Result};
+use semver::Version;
+use serde_json::Value;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The Terraform version recorded in a state file (`terraform_version`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateVersion {
+    pub version: Version,
+    pub path: PathBuf,
+}
+
+impl fmt::Display for StateVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (recorded in {})", self.version, self.path.display())
+    }
+}
+
+/// State files of the configuration in `dir`: the local state of the selected
+/// workspace (`terraform.tfstate`, or `terraform.tfstate.d/<workspace>/`) and the
+/// backend state cached in the data directory (`.terraform/terraform.tfstate`).
+fn state_files(dir: &Path) -> Vec<PathBuf> {
+    let local = match current_workspace(dir) {
+        Some((ws, _)) if ws != "default" => dir
+            .join("terraform.tfstate.d")
+            .join(ws)
+            .join("terraform.tfstate"),
+        _ => dir.join("terraform.tfstate"),
+    };
+    let data_dir = env::var("TF_DATA_DIR").unwrap_or_else(|_| ".terraform".to_string());
+    vec![local, dir.join(data_dir).join("terraform.tfstate")]
+}
+
+/// The newest `terraform_version` recorded in the state files of `dir`, if any.
+pub fn state_version(dir: &Path) -> Result<Option<StateVersion>> {
+    let mut newest: Option<StateVersion> = None;
+    for path in state_files(dir) {
+        if !path.is_file() {
+            continue;
+        }
+        let body = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let json: Value = serde_json::from_str(&body)
+            .with_context(|| format!("failed to parse {}", path.display()))?;
+        let Some(raw) = json.get("terraform_version").and_then(Value::as_str) else {
+            continue;
+        };
+        let version = Version::parse(raw).with_context(|| {
+            format!("invalid terraform_version '{}' in {}", raw, path.display())
+        })?;
+        if newest.as_ref().is_none_or(|n| version > n.version) {
+            newest = Some(StateVersion { version, path });
+        }
+    }
+    Ok(newest)
+}
+
+/// What `exec` does when the selected version is older than the state's
+/// (`TFENV_STATE_CHECK`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateCheck {
+    /// Print a warning and run anyway (default)
+    Warn,
+    /// Fail without running
+    Refuse,
+    /// Do not read the state
+    Off,
+}
+
+impl StateCheck {
+    pub fn from_env() -> Result<Self> {
+        match env::var("TFENV_STATE_CHECK").as_deref() {
+            Err(_) | Ok("") | Ok("warn") => Ok(Self::Warn),
+            Ok("refuse") => Ok(Self::Refuse),
+            Ok("off") => Ok(Self::Off),
+            Ok(other) => anyhow::bail!(
+                "invalid TFENV_STATE_CHECK '{}' (expected warn, refuse or off)",
+                other
+            ),
+        }
+    }
+}
+
+/// Compare the version about to run in `dir` with the version recorded in its state;
+/// an older binary cannot read newer state. Unreadable state only fails `refuse`.
+pub fn check_state_version(dir: &Path, version: &str) -> Result<()> {
+    let check = StateCheck::from_env()?;
+    if check == StateCheck::Off {
+        return Ok(());
+    }
+    let state = match state_version(dir) {
+        Ok(state) => state,
+        Err(e) if check == StateCheck::Warn => {
+            eprintln!("warning: {:#}; state version not checked", e);
+            return Ok(());
+        }
+        Err(e) => return Err(e),
+    };
+    let (Some(state), Ok(selected)) = (state, Version::parse(version)) else {
+        return Ok(());
+    };
+    if selected >= state.version {
+        return Ok(());
+    }
+    let msg = format!(
+        "selected version {} is older than the state's {}",
+        selected, state
+    );
+    match check {
+        StateCheck::Refuse => anyhow::bail!(
+            "{}; pin {} or newer (or set TFENV_STATE_CHECK=warn)",
+            msg,
+            state.version
+        ),
+        _ => {
+            eprintln!("warning: {}", msg);
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use crate::product::Product;
+use anyhow::{Context, Result};
+use std::env;
+use std::fs;
+use std::path::Path;
+
+/// asdf/mise version file, shared by every tool: one `<tool> <version>...` line each.
+pub const TOOL_VERSIONS: &str = ".tool-versions";
+
+/// How `.tool-versions` ranks against the product's own version files
+/// (`TFENV_TOOL_VERSIONS`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolVersionsMode {
+    /// Read before `.terraform-version` in the same directory
+    Prefer,
+    /// Read after `.terraform-version` in the same directory (default)
+    Fallback,
+    /// Not read
+    Ignore,
+}
+
+impl ToolVersionsMode {
+    pub fn from_env() -> Result<Self> {
+        match env::var("TFENV_TOOL_VERSIONS").as_deref() {
+            Err(_) | Ok("") | Ok("fallback") => Ok(Self::Fallback),
+            Ok("prefer") => Ok(Self::Prefer),
+            Ok("ignore") => Ok(Self::Ignore),
+            Ok(other) => anyhow::bail!(
+                "invalid TFENV_TOOL_VERSIONS '{}' (expected prefer, fallback or ignore)",
+                other
+            ),
+        }
+    }
+}
+
+/// Names the product may be listed under: its name and, if different, its binary
+/// (`opentofu` and `tofu`).
+fn tool_names(product: &Product) -> Vec<&str> {
+    let mut names = vec![product.name()];
+    if product.binary() != product.name() {
+        names.push(product.binary());
+    }
+    names
+}
+
+/// The version `.tool-versions` content pins for `product`: the first version on
+/// its line (later ones are asdf fallbacks). `system` and `ref:`/`path:` pins
+/// cannot be installed by tfenv and are treated as no pin.
+pub fn parse_tool_versions(body: &str, product: &Product) -> Option<String> {
+    let names = tool_names(product);
+    for line in body.lines() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        let mut fields = line.split_whitespace();
+        if fields.next().is_some_and(|tool| names.contains(&tool)) {
+            return fields
+                .next()
+                .filter(|v| *v != "system" && !v.contains(':'))
+                .map(str::to_string);
+        }
+    }
+    None
+}
+
+/// The version `path` pins for `product`, if it lists the product.
+pub fn read_tool_versions(path: &Path, product: &Product) -> Result<Option<String>> {
+    let body =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    Ok(parse_tool_versions(&body, product))
+}
+
+/// Pin `product` to `version` in the `.tool-versions` at `path`, replacing its line
+/// (under any of its names) and keeping every other tool's.
+pub fn write_tool_versions(path: &Path, product: &Product, version: &str) -> Result<()> {
+    let names = tool_names(product);
+    let body = if path.exists() {
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?
+    } else {
+        String::new()
+    };
+    let entry = format!("{} {}", product.name(), version);
+    let mut replaced = false;
+    let mut lines: Vec<String> = Vec::new();
+    for line in body.lines() {
+        let tool = line.split_whitespace().next().unwrap_or("");
+        if names.contains(&tool) {
+            if !replaced {
+                lines.push(entry.clone());
+                replaced = true;
+            }
+        } else {
+            lines.push(line.to_string());
+        }
+    }
+    if !replaced {
+        lines.push(entry);
+    }
+    fs::write(path, lines.join("\n") + "\n")
+        .with_context(|| format!("failed to write {}", path.display()))
+}
@@ -0,0 +1,75 @@
+use serde::Serialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// One step taken while resolving a version.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TraceStep {
+    /// What kind of step this is (`env`, `version-file`, `spec`, `local`, `remote`, ...)
+    pub step: &'static str,
+    pub detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+}
+
+/// The steps of a version resolution, for `tfenv version --explain`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Trace {
+    pub steps: Vec<TraceStep>,
+}
+
+impl Trace {
+    pub fn note(&mut self, step: &'static str, detail: impl Into<String>) {
+        self.steps.push(TraceStep {
+            step,
+            detail: detail.into(),
+            path: None,
+        });
+    }
+
+    /// A step about a file: a version file considered, the configuration read, ...
+    pub fn note_path(&mut self, step: &'static str, path: &Path, detail: impl Into<String>) {
+        self.steps.push(TraceStep {
+            step,
+            detail: detail.into(),
+            path: Some(path.to_path_buf()),
+        });
+    }
+
+    /// The trace and the outcome of the resolution as a JSON document.
+    pub fn to_json(&self, outcome: &anyhow::Result<String>) -> serde_json::Result<String> {
+        #[derive(Serialize)]
+        struct Explanation<'a> {
+            steps: &'a [TraceStep],
+            #[serde(skip_serializing_if = "Option::is_none")]
+            version: Option<&'a str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            error: Option<String>,
+        }
+        serde_json::to_string_pretty(&Explanation {
+            steps: &self.steps,
+            version: outcome.as_ref().ok().map(String::as_str),
+            error: outcome.as_ref().err().map(|e| format!("{:#}", e)),
+        })
+    }
+}
+
+impl fmt::Display for Trace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self.steps.iter().map(|s| s.step.len()).max().unwrap_or(0);
+        for s in &self.steps {
+            match &s.path {
+                Some(path) => writeln!(
+                    f,
+                    "{:width$}  {}: {}",
+                    s.step,
+                    path.display(),
+                    s.detail,
+                    width = width
+                )?,
+                None => writeln!(f, "{:width$}  {}", s.step, s.detail, width = width)?,
+            }
+        }
+        Ok(())
+    }
+}
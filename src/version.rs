@@ -1,122 +1,668 @@
+use crate::constraint::{is_constraint_expression, Constraints, Pick};
+use crate::installer::{http_client, normalize_sha256};
+use crate::product::{detect_product, Product};
+use crate::required::{
+    configuration_required_versions, has_required_version, local_module_dirs, RequiredVersion,
+};
+use crate::search::search_dirs;
+use crate::state::state_version;
+use crate::tool_versions::{read_tool_versions, ToolVersionsMode, TOOL_VERSIONS};
+use crate::trace::Trace;
 use anyhow::{Context, Result};
+use ignore::WalkBuilder;
 use regex::Regex;
-use scraper::{Html, Selector};
 use semver::Version;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs;
-use std::path::{Path, PathBuf};
-
-fn find_local_version_file(start: &Path) -> Option<PathBuf> {
-    let mut root = start.to_path_buf();
-    loop {
-        let candidate = root.join(".terraform-version");
-        if candidate.exists() {
-            return Some(candidate);
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+/// The product's version files plus `.tool-versions`, in order of preference
+/// within one directory (see [`ToolVersionsMode`]).
+fn pin_files(product: &Product) -> Result<Vec<String>> {
+    let mut files = product.version_files();
+    match ToolVersionsMode::from_env()? {
+        ToolVersionsMode::Prefer => files.insert(0, TOOL_VERSIONS.to_string()),
+        ToolVersionsMode::Fallback => files.push(TOOL_VERSIONS.to_string()),
+        ToolVersionsMode::Ignore => {}
+    }
+    Ok(files)
+}
+
+/// Whether `path` pins `product`: any version file does, a `.tool-versions` only
+/// if it has a line for the product.
+fn pins_product(path: &Path, product: &Product) -> Result<bool> {
+    if path.file_name().is_some_and(|n| n == TOOL_VERSIONS) {
+        return Ok(read_tool_versions(path, product)?.is_some());
+    }
+    Ok(true)
+}
+
+/// Nearest of the product's pin files from `start` upwards; within one directory
+/// the earlier file wins, and a `<version file>.<workspace>` beats its version file.
+fn find_local_version_file(
+    start: &Path,
+    product: &Product,
+    workspace: Option<&str>,
+    trace: &mut Trace,
+) -> Result<Option<PathBuf>> {
+    let mut file_names = Vec::new();
+    for file_name in pin_files(product)? {
+        if let Some(ws) = workspace.filter(|_| file_name != TOOL_VERSIONS) {
+            file_names.push(format!("{}.{}", file_name, ws));
         }
-        if !root.pop() {
-            break;
+        file_names.push(file_name);
+    }
+    let (dirs, stop) = search_dirs(start);
+    for dir in dirs {
+        for file_name in &file_names {
+            let candidate = dir.join(file_name);
+            if !candidate.exists() {
+                trace.note_path("version-file", &candidate, "not found");
+            } else if pins_product(&candidate, product)? {
+                return Ok(Some(candidate));
+            } else {
+                trace.note_path(
+                    "version-file",
+                    &candidate,
+                    format!("no {} entry", product.name()),
+                );
+            }
         }
     }
-    None
+    trace.note("search", format!("no version file: search {}", stop));
+    Ok(None)
+}
+
+/// A version request as written in `TFENV_TERRAFORM_VERSION` or a version file:
+/// the version spec plus any pinned archive checksums (`1.6.3 sha256:<hex>`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionSpec {
+    pub spec: String,
+    pub sha256: Vec<String>,
 }
 
-pub fn resolve_version_name(tfenv_root: &Path, config_dir: &Path) -> Result<String> {
-    // 1. TFENV_TERRAFORM_VERSION
-    if let Ok(var) = env::var("TFENV_TERRAFORM_VERSION") {
-        if !var.is_empty() {
-            return resolve_requested(&var, tfenv_root, config_dir);
+/// Split a version line into its spec and `sha256:<hex>` pins. Several pins may be
+/// given (e.g. one per platform); the downloaded archive has to match one of them.
+pub fn parse_version_spec(line: &str) -> Result<VersionSpec> {
+    let mut spec: Vec<&str> = Vec::new();
+    let mut sha256 = Vec::new();
+    for token in line.split_whitespace() {
+        if let Some(hex) = token.strip_prefix("sha256:") {
+            sha256.push(normalize_sha256(hex)?);
+        } else {
+            spec.push(token);
         }
     }
-    // 2. find version file
-    let cwd = env::current_dir()?;
-    if let Some(f) = find_local_version_file(&cwd) {
-        let s = fs::read_to_string(&f)?.trim().to_string();
-        if !s.is_empty() {
-            return resolve_requested(&s, tfenv_root, config_dir);
+    Ok(VersionSpec {
+        spec: spec.join(" "),
+        sha256,
+    })
+}
+
+/// The specs of a version file: the first non-empty line (ignoring `#` comments)
+/// is the default, and `[workspace.<name>]` sections pin single workspaces:
+///
+/// ```text
+/// 1.6.6
+/// [workspace.prod] 1.5.7
+/// [workspace.staging]
+/// 1.6.0
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VersionFile {
+    pub default: Option<VersionSpec>,
+    pub workspaces: Vec<(String, VersionSpec)>,
+}
+
+impl VersionFile {
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut file = Self::default();
+        // the section the following lines belong to, until its spec is read
+        let mut section: Option<String> = None;
+        for line in content.lines() {
+            let mut line = line.split('#').next().unwrap_or("").trim();
+            if let Some(rest) = line.strip_prefix('[') {
+                let (header, after) = rest
+                    .split_once(']')
+                    .ok_or_else(|| anyhow::anyhow!("unterminated section '{}'", line))?;
+                let name = header.trim().strip_prefix("workspace.").ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "unknown section '[{}]' (expected [workspace.<name>])",
+                        header
+                    )
+                })?;
+                section = Some(name.to_string());
+                line = after.trim();
+            }
+            if line.is_empty() {
+                continue;
+            }
+            let spec = parse_version_spec(line)?;
+            match section.take() {
+                Some(name) => file.workspaces.push((name, spec)),
+                None if file.default.is_none() => file.default = Some(spec),
+                None => {}
+            }
         }
+        Ok(file)
     }
-    // 3. $HOME/.terraform-version
-    if let Some(home) = dirs::home_dir() {
-        let hf = home.join(".terraform-version");
-        if hf.exists() {
-            let s = fs::read_to_string(hf)?.trim().to_string();
-            if !s.is_empty() {
-                return resolve_requested(&s, tfenv_root, config_dir);
+
+    /// The spec for `workspace`, falling back to the default.
+    pub fn spec_for(&self, workspace: Option<&str>) -> Option<&VersionSpec> {
+        workspace
+            .and_then(|ws| self.workspaces.iter().find(|(name, _)| name == ws))
+            .map(|(_, spec)| spec)
+            .or(self.default.as_ref())
+    }
+}
+
+fn parse_version_file_at(path: &Path) -> Result<VersionFile> {
+    let content =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    VersionFile::parse(&content)
+        .with_context(|| format!("invalid version spec in {}", path.display()))
+}
+
+/// The spec a version file pins for `workspace` (or its default).
+fn read_version_file(path: &Path, workspace: Option<&str>) -> Result<Option<VersionSpec>> {
+    Ok(parse_version_file_at(path)?.spec_for(workspace).cloned())
+}
+
+/// Whether `name` can be a workspace name. Terraform only allows names that are
+/// URL path segments; as they become part of file names (`.terraform-version.<name>`,
+/// `terraform.tfstate.d/<name>`), letters, digits, `-`, `_` and `.` are accepted,
+/// without a leading `.`.
+pub fn is_workspace_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+/// Suffixes left by editors and merge tools next to a version file; never taken
+/// for workspace pins when walking a tree.
+const BACKUP_SUFFIXES: &[&str] = &["swp", "swo", "bak", "orig", "rej", "tmp", "old"];
+
+/// Whether `.terraform-version.<suffix>` found in a tree is a workspace pin.
+fn is_workspace_file_suffix(suffix: &str) -> bool {
+    is_workspace_name(suffix) && !BACKUP_SUFFIXES.contains(&suffix.to_lowercase().as_str())
+}
+
+/// The selected Terraform workspace for `dir` and where it came from: `TF_WORKSPACE`,
+/// else `.terraform/environment` in the data directory (`TF_DATA_DIR`). A name that
+/// is not a valid workspace name (see [`is_workspace_name`]) selects none.
+pub fn current_workspace(dir: &Path) -> Option<(String, String)> {
+    let (ws, source) = match env::var("TF_WORKSPACE") {
+        Ok(ws) if !ws.is_empty() => {
+            let source = format!("TF_WORKSPACE={}", ws);
+            (ws, source)
+        }
+        _ => {
+            let data_dir = env::var("TF_DATA_DIR").unwrap_or_else(|_| ".terraform".to_string());
+            let path = dir.join(data_dir).join("environment");
+            let ws = fs::read_to_string(&path).ok()?.trim().to_string();
+            (ws, path.display().to_string())
+        }
+    };
+    is_workspace_name(&ws).then_some((ws, source))
+}
+
+/// The spec pinned by a version file or, for `.tool-versions`, by the product's line.
+fn read_pin_file(
+    path: &Path,
+    product: &Product,
+    workspace: Option<&str>,
+) -> Result<Option<VersionSpec>> {
+    if path.file_name().is_some_and(|n| n == TOOL_VERSIONS) {
+        return read_tool_versions(path, product)?
+            .map(|v| {
+                parse_version_spec(&v)
+                    .with_context(|| format!("invalid version spec in {}", path.display()))
+            })
+            .transpose();
+    }
+    read_version_file(path, workspace)
+}
+
+/// The resolved version together with any checksums pinned alongside it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedVersion {
+    pub version: String,
+    pub sha256: Vec<String>,
+}
+
+/// Nearest directory from `start` upwards whose configuration constrains the
+/// product's version (e.g. `terragrunt_version_constraint` in `terragrunt.hcl`).
+fn find_constraint_dir(start: &Path, product: &Product) -> Option<PathBuf> {
+    search_dirs(start)
+        .0
+        .into_iter()
+        .find(|dir| has_required_version(dir, product))
+}
+
+/// Directory versions are resolved for: `TFENV_DIR` (relative to the current
+/// directory) if set, else the current directory.
+pub fn working_dir() -> Result<PathBuf> {
+    let cwd = env::current_dir()?;
+    Ok(match env::var("TFENV_DIR") {
+        Ok(dir) if !dir.is_empty() => join_dir(&cwd, dir),
+        _ => cwd,
+    })
+}
+
+/// `dir` relative to `base`, with `.` and `..` collapsed so that upward searches
+/// only visit its real ancestors: canonicalized if it exists, else collapsed
+/// lexically.
+pub fn join_dir(base: &Path, dir: impl AsRef<Path>) -> PathBuf {
+    let joined = base.join(dir);
+    if let Ok(canonical) = joined.canonicalize() {
+        return canonical;
+    }
+    let mut out = PathBuf::new();
+    for component in joined.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
             }
+            c => out.push(c),
         }
     }
-    // default to latest
-    resolve_requested("latest", tfenv_root, config_dir)
-}
-
-pub fn list_remote_versions() -> Result<Vec<(String, String)>> {
-    let product = env::var("TFENV_PRODUCT")
-        .unwrap_or_else(|_| "terraform".to_string())
-        .to_lowercase();
-    let remote = env::var("TFENV_REMOTE").unwrap_or_else(|_| {
-        if product == "terraform" {
-            "https://releases.hashicorp.com/terraform/".to_string()
-        } else if product == "opentofu" {
-            "https://github.com/opentofu/opentofu/releases".to_string()
-        } else {
-            // fallback to HashiCorp-style
-            "https://releases.hashicorp.com/terraform/".to_string()
+    out
+}
+
+/// The directory given by Terraform's `-chdir=<dir>` global option in `args`, if
+/// any; it has to come before the subcommand.
+pub fn chdir_arg(args: &[String]) -> Option<&str> {
+    args.iter()
+        .take_while(|a| a.starts_with('-'))
+        .find_map(|a| {
+            a.strip_prefix("-chdir=")
+                .or_else(|| a.strip_prefix("--chdir="))
+        })
+}
+
+/// The requested spec and the directory its `min-required`/`latest-allowed`
+/// constraints are read from.
+fn requested_version_spec(
+    product: &Product,
+    config_dir: &Path,
+    start: &Path,
+    trace: &mut Trace,
+) -> Result<(VersionSpec, PathBuf)> {
+    let cwd = start.to_path_buf();
+    trace.note_path("start", &cwd, "resolving for this directory");
+    // 1. TFENV_<PRODUCT>_VERSION
+    let var_name = product.version_env();
+    match env::var(&var_name) {
+        Ok(var) if !var.is_empty() => {
+            trace.note("env", format!("{}={} (wins)", var_name, var));
+            let spec = parse_version_spec(&var).with_context(|| format!("invalid {}", var_name))?;
+            return Ok((spec, cwd));
         }
-    });
-    let body = reqwest::blocking::get(&remote)?.text()?;
-    let doc = Html::parse_document(&body);
-    let selector = Selector::parse("a").unwrap();
-    let mut versions: Vec<Version> = Vec::new();
-    for el in doc.select(&selector) {
-        if let Some(href) = el.value().attr("href") {
-            if product == "terraform" {
-                if let Some(caps) = href.strip_prefix("/terraform/") {
-                    let v = caps.trim_end_matches('/');
-                    if let Ok(vers) = Version::parse(v) {
-                        versions.push(vers);
-                    }
-                }
-            } else if product == "opentofu" {
-                // look for GitHub release tag links like /opentofu/opentofu/releases/tag/vX.Y.Z
-                if let Some(pos) = href.find("/opentofu/opentofu/releases/tag/v") {
-                    let v = &href[pos + "/opentofu/opentofu/releases/tag/v".len()..];
-                    let v = v.trim_end_matches('/');
-                    if let Ok(vers) = Version::parse(v) {
-                        versions.push(vers);
-                    }
+        _ => trace.note("env", format!("{} is not set", var_name)),
+    }
+    let workspace = match current_workspace(&cwd) {
+        Some((ws, source)) => {
+            trace.note("workspace", format!("{} (from {})", ws, source));
+            Some(ws)
+        }
+        None => {
+            trace.note("workspace", "none selected");
+            None
+        }
+    };
+    let workspace = workspace.as_deref();
+    // 2. find version file, or for products pinned by their configuration the
+    // nearest constraint; a version file in the constraint's directory or below wins
+    let version_file = find_local_version_file(&cwd, product, workspace, trace)?;
+    let constraint_dir = if product.resolves_from_constraint() {
+        find_constraint_dir(&cwd, product)
+    } else {
+        None
+    };
+    match (version_file, constraint_dir) {
+        (Some(f), Some(dir)) if !f.starts_with(&dir) => {
+            trace.note_path(
+                "constraint",
+                &dir,
+                format!(
+                    "{} is nearer than {} (wins, as latest-allowed)",
+                    product.constraint_attribute(),
+                    f.display()
+                ),
+            );
+            return Ok((parse_version_spec("latest-allowed")?, dir));
+        }
+        (Some(f), _) => {
+            if let Some(spec) = read_pin_file(&f, product, workspace)? {
+                trace.note_path("version-file", &f, "wins");
+                return Ok((spec, cwd));
+            }
+            trace.note_path("version-file", &f, "no pin, ignored");
+        }
+        (None, Some(dir)) => {
+            trace.note_path(
+                "constraint",
+                &dir,
+                format!(
+                    "{} found (wins, as latest-allowed)",
+                    product.constraint_attribute()
+                ),
+            );
+            return Ok((parse_version_spec("latest-allowed")?, dir));
+        }
+        (None, None) => {}
+    }
+    // 3. $HOME/.terraform-version (or the product's other pin files)
+    if let Some(home) = dirs::home_dir() {
+        for file_name in pin_files(product)? {
+            let hf = home.join(file_name);
+            if hf.exists() {
+                if let Some(spec) = read_pin_file(&hf, product, workspace)? {
+                    trace.note_path("home-file", &hf, "wins");
+                    return Ok((spec, cwd));
                 }
+                trace.note_path("home-file", &hf, "no pin, ignored");
+            } else {
+                trace.note_path("home-file", &hf, "not found");
             }
         }
     }
-    versions.sort();
-    versions.reverse();
-    Ok(versions
+    // 4. default set by `tfenv use`
+    let default = product.default_version_path(config_dir);
+    if default.exists() {
+        if let Some(spec) = read_version_file(&default, workspace)? {
+            trace.note_path("default", &default, "wins");
+            return Ok((spec, cwd));
+        }
+    }
+    trace.note_path("default", &default, "not set");
+    // default to latest
+    trace.note("fallback", "nothing pins a version; using latest");
+    Ok((parse_version_spec("latest")?, cwd))
+}
+
+/// Resolve the version selected for [`working_dir`].
+pub fn resolve_version(
+    tfenv_root: &Path,
+    config_dir: &Path,
+    product: &Product,
+) -> Result<ResolvedVersion> {
+    resolve_version_in(tfenv_root, config_dir, product, &working_dir()?)
+}
+
+/// Resolve the version selected for `dir`.
+pub fn resolve_version_in(
+    tfenv_root: &Path,
+    config_dir: &Path,
+    product: &Product,
+    dir: &Path,
+) -> Result<ResolvedVersion> {
+    resolve_version_traced(tfenv_root, config_dir, product, dir, &mut Trace::default())
+}
+
+/// [`resolve_version_in`], recording every step in `trace` (`tfenv version --explain`).
+pub fn resolve_version_traced(
+    tfenv_root: &Path,
+    config_dir: &Path,
+    product: &Product,
+    start: &Path,
+    trace: &mut Trace,
+) -> Result<ResolvedVersion> {
+    let (spec, dir) = requested_version_spec(product, config_dir, start, trace)?;
+    trace.note("spec", format!("requested '{}'", spec.spec));
+    if !spec.sha256.is_empty() {
+        trace.note(
+            "spec",
+            format!("pinned checksums: sha256:{}", spec.sha256.join(", sha256:")),
+        );
+    }
+    let version = resolve_requested_with(
+        &spec.spec, &dir, tfenv_root, config_dir, product, true, trace,
+    )?;
+    trace.note("result", version.clone());
+    Ok(ResolvedVersion {
+        version,
+        sha256: spec.sha256,
+    })
+}
+
+pub fn resolve_version_name(
+    tfenv_root: &Path,
+    config_dir: &Path,
+    product: &Product,
+) -> Result<String> {
+    Ok(resolve_version(tfenv_root, config_dir, product)?.version)
+}
+
+static INDEX_CACHE: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+
+/// Fetch a remote release listing once per process; parallel installs resolving
+/// several `latest:` specs then share a single request.
+fn fetch_remote_index(url: &str) -> Result<String> {
+    let cache = INDEX_CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Some(body) = cache.lock().unwrap().get(url) {
+        return Ok(body.clone());
+    }
+    let resp = http_client()?
+        .get(url)
+        .send()
+        .with_context(|| format!("failed to fetch {}", url))?;
+    if !resp.status().is_success() {
+        anyhow::bail!("Failed to fetch {}: HTTP {}", url, resp.status());
+    }
+    let body = resp.text()?;
+    cache.lock().unwrap().insert(url.to_string(), body.clone());
+    Ok(body)
+}
+
+pub fn list_remote_versions(product: &Product) -> Result<Vec<(String, String)>> {
+    let body = fetch_remote_index(&product.listing_url())?;
+    Ok(product
+        .parse_listing(&body)
         .into_iter()
-        .map(|v| (v.to_string(), product.clone()))
+        .map(|v| (v.to_string(), product.name().to_string()))
         .collect())
 }
 
-fn resolve_requested(
+/// A version requirement found while walking a directory tree: either a version
+/// file or a directory whose Terraform files declare `required_version`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreePin {
+    /// The version file or the directory holding the `.tf` files
+    pub source: PathBuf,
+    /// Directory the spec is resolved in (for `min-required`/`latest-allowed`)
+    pub dir: PathBuf,
+    pub spec: String,
+}
+
+/// Walk `root`, honouring `.gitignore` and skipping `.git`/`.terraform`, and collect
+/// every version file of `product` and `required_version` (or equivalent) constraint
+/// below it. A constraint only yields a `latest-allowed` pin for a directory a stack
+/// could run in: not for local modules called by another configuration, and not
+/// below a version file pinning a version (rather than `min-required`/`latest-allowed`).
+pub fn collect_tree_pins(root: &Path, product: &Product) -> Result<Vec<TreePin>> {
+    tree_pins(root, product, false)
+}
+
+/// [`collect_tree_pins`], keeping only the directories [`detect_product`] assigns to
+/// `product` (Terraform where nothing is detected), so that each stack of a mixed
+/// Terraform/OpenTofu tree is installed for its own product.
+pub fn collect_detected_tree_pins(root: &Path, product: &Product) -> Result<Vec<TreePin>> {
+    tree_pins(root, product, true)
+}
+
+fn tree_pins(root: &Path, product: &Product, detected_only: bool) -> Result<Vec<TreePin>> {
+    let pin_files = pin_files(product)?;
+    let mut pins = Vec::new();
+    let mut constraint_dirs = Vec::new();
+    let mut module_dirs = HashSet::new();
+    let mut pinned_dirs = HashSet::new();
+    let walker = WalkBuilder::new(root)
+        .hidden(false)
+        .require_git(false)
+        .filter_entry(|e| {
+            let name = e.file_name();
+            !(e.file_type().is_some_and(|t| t.is_dir()) && (name == ".git" || name == ".terraform"))
+        })
+        .build();
+    for entry in walker {
+        let entry = entry.with_context(|| format!("failed to walk {}", root.display()))?;
+        let path = entry.path();
+        if entry.file_type().is_some_and(|t| t.is_dir()) {
+            if has_required_version(path, product) {
+                constraint_dirs.push(path.to_path_buf());
+            }
+            // unparsable configurations are reported when their pin is resolved
+            module_dirs.extend(local_module_dirs(path, product).unwrap_or_default());
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy();
+        // version files, `<version file>.<workspace>` files and `.tool-versions`
+        let is_pin_file = pin_files.iter().any(|f| {
+            name == f.as_str()
+                || (f != TOOL_VERSIONS
+                    && name
+                        .strip_prefix(&format!("{}.", f))
+                        .is_some_and(is_workspace_file_suffix))
+        });
+        if !is_pin_file {
+            continue;
+        }
+        // every workspace's pin, so that all of them get installed
+        let specs: Result<Vec<VersionSpec>> = if name == TOOL_VERSIONS {
+            read_pin_file(path, product, None).map(|spec| spec.into_iter().collect())
+        } else {
+            parse_version_file_at(path).map(|file| {
+                file.default
+                    .into_iter()
+                    .chain(file.workspaces.into_iter().map(|(_, spec)| spec))
+                    .collect()
+            })
+        };
+        // one broken file should not keep every other pin from being installed
+        let specs = match specs {
+            Ok(specs) => specs,
+            Err(e) => {
+                eprintln!("warning: {:#}; skipped", e);
+                continue;
+            }
+        };
+        let dir = path.parent().unwrap_or(root).to_path_buf();
+        if specs
+            .iter()
+            .any(|s| s.spec != "min-required" && s.spec != "latest-allowed")
+        {
+            pinned_dirs.insert(dir.clone());
+        }
+        for spec in specs {
+            pins.push(TreePin {
+                source: path.to_path_buf(),
+                dir: dir.clone(),
+                spec: spec.spec,
+            });
+        }
+    }
+    for dir in constraint_dirs {
+        let pinned = dir
+            .ancestors()
+            .take_while(|d| d.starts_with(root))
+            .any(|d| pinned_dirs.contains(d));
+        let module = dir.canonicalize().is_ok_and(|d| module_dirs.contains(&d));
+        if !pinned && !module {
+            pins.push(TreePin {
+                source: dir.clone(),
+                dir,
+                spec: "latest-allowed".to_string(),
+            });
+        }
+    }
+    if detected_only {
+        pins.retain(|pin| {
+            detect_product(&pin.dir).map_or("terraform", |(name, _, _)| name) == product.name()
+        });
+    }
+    pins.sort_by(|a, b| a.source.cmp(&b.source));
+    Ok(pins)
+}
+
+/// Resolve a spec passed to `install`, reading `min-required`/`latest-allowed`
+/// constraints from `dir`. Unlike `exec`/`version`, `latest` specs are matched
+/// against the remote listing, as `tfenv install latest` does.
+pub fn resolve_install_spec(
     requested: &str,
+    dir: &Path,
+    tfenv_root: &Path,
+    config_dir: &Path,
+    product: &Product,
+) -> Result<String> {
+    resolve_requested_with(
+        requested,
+        dir,
+        tfenv_root,
+        config_dir,
+        product,
+        false,
+        &mut Trace::default(),
+    )
+}
+
+fn resolve_requested_with(
+    requested: &str,
+    dir: &Path,
     _tfenv_root: &Path,
     config_dir: &Path,
+    product: &Product,
+    prefer_local: bool,
+    trace: &mut Trace,
 ) -> Result<String> {
     let mut req = requested.to_string();
     if req.starts_with('v') {
         req = req.trim_start_matches('v').to_string();
     }
 
-    if req == "min-required" {
-        if let Some(min) = min_required(config_dir)? {
-            return Ok(min);
-        }
-        anyhow::bail!("min-required could not be determined");
+    if req == "min-required" || req == "latest-allowed" {
+        return resolve_constrained(&req, dir, config_dir, product, prefer_local, trace);
     }
 
-    if req == "latest-allowed" {
-        if let Some(mapped) = latest_allowed_to_requested(config_dir)? {
-            req = mapped;
+    if req == "state" {
+        if !product.reads_terraform_state() {
+            anyhow::bail!(
+                "'state' requested but {} does not use Terraform state",
+                product.name()
+            );
         }
+        let state = state_version(dir)?.ok_or_else(|| {
+            anyhow::anyhow!(
+                "'state' requested but no Terraform state records a version in {}",
+                dir.display()
+            )
+        })?;
+        trace.note_path(
+            "expand",
+            &state.path,
+            format!(
+                "'state' is the recorded terraform_version {}",
+                state.version
+            ),
+        );
+        return Ok(state.version.to_string());
+    }
+
+    if is_constraint_expression(&req) {
+        let constraints = Constraints::parse(&req)
+            .with_context(|| format!("invalid version constraint '{}'", req))?;
+        trace.note(
+            "expand",
+            format!("constraint expression '{}', newest match wins", constraints),
+        );
+        return newest_allowed(&constraints, config_dir, product, prefer_local, trace)
+            .map(|v| v.to_string())
+            .with_context(|| format!("cannot resolve '{}' for {}", req, product.name()));
     }
 
     if req.starts_with("latest") {
@@ -127,198 +673,254 @@ fn resolve_requested(
                 regex = req[i + 1..].to_string();
             }
         }
+        trace.note(
+            "expand",
+            format!("'{}' is the newest version matching /{}/", req, regex),
+        );
+        if !prefer_local {
+            return latest_remote_matching(product, &regex, trace)?.ok_or_else(|| {
+                anyhow::anyhow!("No versions matching '{}' found in remote", regex)
+            });
+        }
         // First prefer locally installed matching version
-        if let Some(local) = latest_local_matching(config_dir, &regex)? {
+        if let Some(local) =
+            latest_local_matching(&product.versions_dir(config_dir), &regex, trace)?
+        {
             return Ok(local);
         }
         // If TFENV_AUTO_INSTALL true, look remote
         let auto = env::var("TFENV_AUTO_INSTALL").unwrap_or_else(|_| "true".to_string());
         if auto == "true" {
-            if let Some(remote) = latest_remote_matching(&regex)? {
+            if let Some(remote) = latest_remote_matching(product, &regex, trace)? {
                 return Ok(remote);
             }
             anyhow::bail!("No versions matching '{}' found in remote", regex);
         }
+        trace.note("remote", "not consulted: auto-install disabled");
         anyhow::bail!(
             "No installed versions matched '{}' and auto-install disabled",
             regex
         );
     }
 
+    trace.note("expand", format!("'{}' is an exact version", req));
     Ok(req)
 }
 
-fn latest_local_matching(config_dir: &Path, regex: &str) -> Result<Option<String>> {
-    let versions_dir = config_dir.join("versions");
-    if !versions_dir.exists() {
-        return Ok(None);
+fn version_list(versions: &[Version]) -> String {
+    if versions.is_empty() {
+        return "none".to_string();
     }
+    let mut sorted = versions.to_vec();
+    sorted.sort();
+    let names: Vec<String> = sorted.iter().map(|v| v.to_string()).collect();
+    names.join(", ")
+}
+
+fn latest_local_matching(
+    versions_dir: &Path,
+    regex: &str,
+    trace: &mut Trace,
+) -> Result<Option<String>> {
     let re = Regex::new(regex).context("invalid regex for latest matching")?;
-    let mut candidates: Vec<Version> = Vec::new();
+    let installed = installed_versions(versions_dir)?;
+    let matching: Vec<Version> = installed
+        .iter()
+        .filter(|v| re.is_match(&v.to_string()))
+        .cloned()
+        .collect();
+    trace.note_path(
+        "local",
+        versions_dir,
+        format!(
+            "installed: {}; matching: {}",
+            version_list(&installed),
+            version_list(&matching)
+        ),
+    );
+    Ok(matching.into_iter().max().map(|v| v.to_string()))
+}
+
+fn latest_remote_matching(
+    product: &Product,
+    regex: &str,
+    trace: &mut Trace,
+) -> Result<Option<String>> {
+    let remote = remote_versions(product, trace)?;
+    let re = Regex::new(regex).context("invalid regex for latest remote matching")?;
+    Ok(remote
+        .into_iter()
+        .map(|v| v.to_string())
+        .find(|v| re.is_match(v)))
+}
+
+/// Intersection of every constraint declared by the configuration in `dir` and its
+/// modules, with where each came from.
+fn required_constraints(
+    dir: &Path,
+    product: &Product,
+) -> Result<(Constraints, Vec<RequiredVersion>)> {
+    let found = configuration_required_versions(dir, product)?;
+    let mut constraints = Constraints::default();
+    for required in &found {
+        constraints.intersect(Constraints::parse(&required.spec).with_context(|| {
+            format!(
+                "invalid {} '{}' in {}",
+                product.constraint_attribute(),
+                required.spec,
+                required.file.display()
+            )
+        })?);
+    }
+    Ok((constraints, found))
+}
+
+fn installed_versions(versions_dir: &Path) -> Result<Vec<Version>> {
+    let mut versions = Vec::new();
+    if !versions_dir.exists() {
+        return Ok(versions);
+    }
     for entry in fs::read_dir(versions_dir)? {
         let e = entry?;
         if e.path().is_dir() {
-            if let Some(name) = e.file_name().to_str() {
-                if re.is_match(name) {
-                    if let Ok(v) = Version::parse(name) {
-                        candidates.push(v);
-                    }
-                }
+            if let Some(v) = e.file_name().to_str().and_then(|n| Version::parse(n).ok()) {
+                versions.push(v);
             }
         }
     }
-    candidates.sort();
-    candidates.reverse();
-    Ok(candidates.first().map(|v| v.to_string()))
+    Ok(versions)
 }
 
-fn latest_remote_matching(regex: &str) -> Result<Option<String>> {
-    let product = env::var("TFENV_PRODUCT")
-        .unwrap_or_else(|_| "terraform".to_string())
-        .to_lowercase();
-    let remote = env::var("TFENV_REMOTE").unwrap_or_else(|_| {
-        if product == "terraform" {
-            "https://releases.hashicorp.com/terraform/".to_string()
-        } else if product == "opentofu" {
-            "https://github.com/opentofu/opentofu/releases".to_string()
-        } else {
-            "https://releases.hashicorp.com/terraform/".to_string()
-        }
-    });
-    let body = reqwest::blocking::get(&remote)?.text()?;
-    let doc = Html::parse_document(&body);
-    let selector = Selector::parse("a").unwrap();
-    let re = Regex::new(regex).context("invalid regex for latest remote matching")?;
-    let mut versions: Vec<Version> = Vec::new();
-    for el in doc.select(&selector) {
-        if let Some(href) = el.value().attr("href") {
-            if product == "terraform" {
-                if let Some(caps) = href.strip_prefix("/terraform/") {
-                    let v = caps.trim_end_matches('/');
-                    if re.is_match(v) {
-                        if let Ok(vers) = Version::parse(v) {
-                            versions.push(vers);
-                        }
-                    }
-                }
-            } else if product == "opentofu" {
-                if let Some(pos) = href.find("/opentofu/opentofu/releases/tag/v") {
-                    let v = &href[pos + "/opentofu/opentofu/releases/tag/v".len()..];
-                    let v = v.trim_end_matches('/');
-                    if re.is_match(v) {
-                        if let Ok(vers) = Version::parse(v) {
-                            versions.push(vers);
-                        }
-                    }
-                }
-            }
-        }
-    }
-    versions.sort();
-    versions.reverse();
-    Ok(versions.first().map(|v| v.to_string()))
+fn remote_versions(product: &Product, trace: &mut Trace) -> Result<Vec<Version>> {
+    let url = product.listing_url();
+    let versions = product.parse_listing(&fetch_remote_index(&url)?);
+    trace.note(
+        "remote",
+        format!("consulted {}: {} versions", url, versions.len()),
+    );
+    Ok(versions)
 }
 
-fn min_required(_config_dir: &Path) -> Result<Option<String>> {
-    // search TFENV_DIR (cwd) and config_dir? We'll search cwd
-    let cwd = env::current_dir()?;
-    let mut combined = String::new();
-    // read *.tf and *.tf.json in cwd
-    if let Ok(entries) = fs::read_dir(&cwd) {
-        for ent in entries.flatten() {
-            if let Some(name) = ent.file_name().to_str() {
-                if name.ends_with(".tf") || name.ends_with(".tf.json") {
-                    if let Ok(s) = fs::read_to_string(ent.path()) {
-                        combined.push_str(&s);
-                        combined.push('\n');
-                    }
-                }
-            }
-        }
-    }
-    // find lines with required_version
-    let mut versions: Vec<String> = Vec::new();
-    let re_line =
-        Regex::new(r#"(?m)^\s*[^#]*required_version\s*[:=]?\s*\(?"?(?P<spec>[^"]+)"?\)?"#).unwrap();
-    for cap in re_line.captures_iter(&combined) {
-        if let Some(spec) = cap.name("spec") {
-            versions.push(spec.as_str().to_string());
+/// The newest version satisfying `constraints`: installed ones first when
+/// `prefer_local` (like `latest`), then the remote listing unless auto-install is off.
+fn newest_allowed(
+    constraints: &Constraints,
+    config_dir: &Path,
+    product: &Product,
+    prefer_local: bool,
+    trace: &mut Trace,
+) -> Result<Version> {
+    let versions_dir = product.versions_dir(config_dir);
+    let installed = installed_versions(&versions_dir)?;
+    if prefer_local {
+        let matching: Vec<Version> = installed
+            .iter()
+            .filter(|v| constraints.matches(v))
+            .cloned()
+            .collect();
+        trace.note_path(
+            "local",
+            &versions_dir,
+            format!(
+                "installed: {}; matching: {}",
+                version_list(&installed),
+                version_list(&matching)
+            ),
+        );
+        if let Some(v) = matching.into_iter().max() {
+            return Ok(v);
         }
     }
-    if versions.is_empty() {
-        return Ok(None);
-    }
-    // take first found, attempt to extract numeric part
-    let first = &versions[0];
-    // use find numeric sequence
-    let re_ver = Regex::new(r"([~=!<>]{0,2}\s*)([0-9]+(?:\.[0-9]+){0,2})(-[a-z]+[0-9]+)?").unwrap();
-    if let Some(cap) = re_ver.captures(first) {
-        let qualifier = cap.get(1).map(|m| m.as_str()).unwrap_or("");
-        if qualifier.trim_start().starts_with("!=") {
-            return Ok(None);
-        }
-        let mut found = cap.get(2).map(|m| m.as_str()).unwrap_or("").to_string();
-        if let Some(post) = cap.get(3) {
-            found.push_str(post.as_str());
-        }
-        // pad to x.y.z
-        let pad_re = Regex::new(r"^[0-9]+\.[0-9]+\.[0-9]+$").unwrap();
-        while !pad_re.is_match(&found) {
-            found.push_str(".0");
-        }
-        return Ok(Some(found));
+    let auto = env::var("TFENV_AUTO_INSTALL").unwrap_or_else(|_| "true".to_string());
+    if !prefer_local || auto == "true" {
+        constraints.select(&remote_versions(product, trace)?, Pick::Max)
+    } else {
+        trace.note("remote", "not consulted: auto-install disabled");
+        constraints
+            .select(&installed, Pick::Max)
+            .context("auto-install disabled")
     }
-    Ok(None)
 }
 
-fn latest_allowed_to_requested(_config_dir: &Path) -> Result<Option<String>> {
-    // replicate tfenv-resolve-version's logic for latest-allowed
-    // find required_version spec
-    let cwd = env::current_dir()?;
-    let mut spec_line = String::new();
-    if let Ok(entries) = fs::read_dir(&cwd) {
-        for ent in entries.flatten() {
-            if let Some(name) = ent.file_name().to_str() {
-                if name.ends_with(".tf") || name.ends_with(".tf.json") {
-                    if let Ok(s) = fs::read_to_string(ent.path()) {
-                        for line in s.lines() {
-                            if line.contains("required_version") {
-                                spec_line = line.to_string();
-                                break;
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
-    if spec_line.is_empty() {
-        return Ok(None);
+/// Resolve `min-required` (the oldest release satisfying the constraints in `dir`)
+/// or `latest-allowed` (the newest, preferring installed versions like `latest`).
+fn resolve_constrained(
+    requested: &str,
+    dir: &Path,
+    config_dir: &Path,
+    product: &Product,
+    prefer_local: bool,
+    trace: &mut Trace,
+) -> Result<String> {
+    let (constraints, found) = required_constraints(dir, product)?;
+    for required in &found {
+        trace.note_path(
+            "constraint",
+            &required.file,
+            format!("{} = \"{}\"", product.constraint_attribute(), required.spec),
+        );
     }
-    // crude extract version spec between quotes
-    let parts: Vec<&str> = spec_line.split('"').collect();
-    let version_spec = if parts.len() >= 2 {
-        parts[1]
+    trace.note(
+        "expand",
+        format!(
+            "{}: {} version satisfying '{}'",
+            requested,
+            if requested == "min-required" {
+                "oldest"
+            } else {
+                "newest"
+            },
+            constraints
+        ),
+    );
+    let installed = || installed_versions(&product.versions_dir(config_dir));
+    let version = if requested == "min-required" {
+        if constraints.is_empty() {
+            anyhow::bail!(
+                "min-required could not be determined: no {} found in {}",
+                product.constraint_attribute(),
+                dir.display()
+            );
+        }
+        // the minimum is a property of the releases; only fall back to what is
+        // installed when the listing cannot be fetched
+        let candidates = match remote_versions(product, trace) {
+            Ok(remote) => remote,
+            Err(e) => {
+                eprintln!("{:#}; selecting min-required from installed versions", e);
+                trace.note(
+                    "remote",
+                    format!("listing unavailable ({:#}); using installed versions", e),
+                );
+                installed()?
+            }
+        };
+        constraints.select(&candidates, Pick::Min)
     } else {
-        spec_line.as_str()
+        newest_allowed(&constraints, config_dir, product, prefer_local, trace)
     };
-    let version_num = Regex::new(r"[0-9.]+")?
-        .find(version_spec)
-        .map(|m| m.as_str())
-        .unwrap_or("");
-    // determine mapping
-    if version_spec.trim_start().starts_with('>') {
-        return Ok(Some("latest".to_string()));
-    }
-    if version_spec.trim_start().starts_with("<=") || version_spec.trim_start().starts_with('<') {
-        return Ok(Some(version_num.to_string()));
-    }
-    if version_spec.trim_start().starts_with("~>") {
-        // remove rightmost component
-        if let Some(pos) = version_num.rfind('.') {
-            let prefix = &version_num[..pos];
-            return Ok(Some(format!("latest:^{}\\.", prefix)));
+    let version = version.with_context(|| {
+        let mut msg = format!(
+            "cannot resolve {} for {} in {}",
+            requested,
+            product.name(),
+            dir.display()
+        );
+        if !found.is_empty() {
+            let sources: Vec<String> = found
+                .iter()
+                .map(|r| {
+                    let file = r.file.strip_prefix(dir).unwrap_or(&r.file);
+                    format!("'{}' in {}", r.spec, file.display())
+                })
+                .collect();
+            msg.push_str(&format!(
+                " ({} {})",
+                product.constraint_attribute(),
+                sources.join(", ")
+            ));
         }
-    }
-    Ok(None)
+        msg
+    })?;
+    Ok(version.to_string())
 }
@@ -0,0 +1,162 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use tfenv_rs::archive::{extract_archive, ArchiveFormat};
+
+fn write_zip(path: &Path, entries: &[(&str, &[u8])]) {
+    let mut zip = zip::ZipWriter::new(fs::File::create(path).unwrap());
+    for (name, data) in entries {
+        zip.start_file(*name, zip::write::FileOptions::default())
+            .unwrap();
+        zip.write_all(data).unwrap();
+    }
+    zip.finish().unwrap();
+}
+
+fn write_tar_gz(path: &Path, entries: &[(&str, &[u8])]) {
+    let gz = flate2::write::GzEncoder::new(
+        fs::File::create(path).unwrap(),
+        flate2::Compression::default(),
+    );
+    let mut tar = tar::Builder::new(gz);
+    for (name, data) in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar.append_data(&mut header, name, *data).unwrap();
+    }
+    tar.into_inner().unwrap().finish().unwrap();
+}
+
+#[test]
+fn test_archive_format_parse_and_sniff() {
+    assert_eq!(ArchiveFormat::parse("tgz"), Some(ArchiveFormat::TarGz));
+    assert_eq!(ArchiveFormat::parse("ZIP"), Some(ArchiveFormat::Zip));
+    assert_eq!(ArchiveFormat::parse("binary"), Some(ArchiveFormat::Binary));
+    assert_eq!(ArchiveFormat::parse("rar"), None);
+    assert_eq!(ArchiveFormat::TarGz.extension(), ".tar.gz");
+
+    let tmp = tempfile::TempDir::new().unwrap();
+    let zip = tmp.path().join("a.zip");
+    let tgz = tmp.path().join("a.tar.gz");
+    let raw = tmp.path().join("a");
+    write_zip(&zip, &[("terraform", b"zip")]);
+    write_tar_gz(&tgz, &[("terraform", b"tgz")]);
+    fs::write(&raw, b"#!/bin/sh\n").unwrap();
+    assert_eq!(ArchiveFormat::sniff(&zip).unwrap(), ArchiveFormat::Zip);
+    assert_eq!(ArchiveFormat::sniff(&tgz).unwrap(), ArchiveFormat::TarGz);
+    assert_eq!(ArchiveFormat::sniff(&raw).unwrap(), ArchiveFormat::Binary);
+}
+
+#[test]
+fn test_extract_archive_from_each_format() {
+    let tmp = tempfile::TempDir::new().unwrap();
+    let zip = tmp.path().join("a.zip");
+    let tgz = tmp.path().join("a.tar.gz");
+    let raw = tmp.path().join("raw");
+    write_zip(&zip, &[("LICENSE.txt", b"l"), ("terraform", b"from-zip")]);
+    write_tar_gz(&tgz, &[("dist/terraform", b"from-tgz")]);
+    fs::write(&raw, b"from-raw").unwrap();
+
+    for (archive, format, expected) in [
+        (&zip, ArchiveFormat::Zip, "from-zip"),
+        (&tgz, ArchiveFormat::TarGz, "from-tgz"),
+        (&raw, ArchiveFormat::Binary, "from-raw"),
+    ] {
+        let out = tmp.path().join(format!("out-{}", expected));
+        extract_archive(archive, &out, "terraform", format).unwrap();
+        assert_eq!(fs::read_to_string(out.join("terraform")).unwrap(), expected);
+    }
+    // the whole archive is kept, not only the binary
+    let files = extract_archive(
+        &zip,
+        &tmp.path().join("full"),
+        "terraform",
+        ArchiveFormat::Zip,
+    )
+    .unwrap();
+    assert_eq!(files, vec!["LICENSE.txt", "terraform"]);
+    assert!(tmp.path().join("full/LICENSE.txt").exists());
+
+    let missing = tmp.path().join("missing.zip");
+    write_zip(&missing, &[("terraform-provider-x", b"")]);
+    assert!(extract_archive(
+        &missing,
+        &tmp.path().join("out"),
+        "terraform",
+        ArchiveFormat::Zip
+    )
+    .is_err());
+}
+
+#[test]
+fn test_extract_archive_uses_declared_format() {
+    let tmp = tempfile::TempDir::new().unwrap();
+    let zip = tmp.path().join("a.zip");
+    write_zip(&zip, &[("terraform", b"from-zip")]);
+
+    // a product publishing raw binaries gets the file as-is, whatever it contains
+    let out = tmp.path().join("raw");
+    let files = extract_archive(&zip, &out, "terraform", ArchiveFormat::Binary).unwrap();
+    assert_eq!(files, vec!["terraform"]);
+    assert_eq!(
+        fs::read(out.join("terraform")).unwrap(),
+        fs::read(&zip).unwrap()
+    );
+    // and a download that is not what the product publishes is an error
+    let tgz = tmp.path().join("tgz");
+    assert!(extract_archive(&zip, &tgz, "terraform", ArchiveFormat::TarGz).is_err());
+
+    assert_eq!(
+        ArchiveFormat::from_file_name("tofu_1.6.2_linux_amd64.tar.gz"),
+        Some(ArchiveFormat::TarGz)
+    );
+    assert_eq!(ArchiveFormat::from_file_name("terraform"), None);
+}
+
+#[test]
+fn test_extract_archive_rejects_traversal() {
+    let tmp = tempfile::TempDir::new().unwrap();
+    let zip = tmp.path().join("evil.zip");
+    write_zip(&zip, &[("terraform", b"x"), ("../escape.txt", b"x")]);
+    let out = tmp.path().join("out");
+    assert!(extract_archive(&zip, &out, "terraform", ArchiveFormat::Zip).is_err());
+    assert!(!tmp.path().join("escape.txt").exists());
+
+    let abs = tmp.path().join("abs.zip");
+    write_zip(&abs, &[("/etc/terraform", b"x")]);
+    assert!(extract_archive(
+        &abs,
+        &tmp.path().join("out2"),
+        "terraform",
+        ArchiveFormat::Zip
+    )
+    .is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_extract_archive_preserves_exec_bits() {
+    use std::os::unix::fs::PermissionsExt;
+    let tmp = tempfile::TempDir::new().unwrap();
+    let zip = tmp.path().join("a.zip");
+    let mut w = zip::ZipWriter::new(fs::File::create(&zip).unwrap());
+    let exec = zip::write::FileOptions::default().unix_permissions(0o755);
+    let plain = zip::write::FileOptions::default().unix_permissions(0o644);
+    w.start_file("terraform", plain).unwrap();
+    w.write_all(b"bin").unwrap();
+    w.start_file("helper", exec).unwrap();
+    w.write_all(b"sh").unwrap();
+    w.start_file("LICENSE.txt", plain).unwrap();
+    w.write_all(b"l").unwrap();
+    w.finish().unwrap();
+
+    let out = tmp.path().join("out");
+    extract_archive(&zip, &out, "terraform", ArchiveFormat::Zip).unwrap();
+    let mode = |name: &str| fs::metadata(out.join(name)).unwrap().permissions().mode() & 0o777;
+    assert_eq!(mode("helper"), 0o755);
+    assert_eq!(mode("LICENSE.txt"), 0o644);
+    // the product binary is always executable
+    assert_eq!(mode("terraform"), 0o755);
+}
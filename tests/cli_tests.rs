@@ -0,0 +1,216 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// `tfenv` to run in `cwd` with its root and config in `config`, isolated from the
+/// caller's pins and settings.
+fn tfenv(config: &Path, cwd: &Path) -> Command {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_tfenv-rs"));
+    for (key, _) in std::env::vars() {
+        if key.starts_with("TFENV_") || key.starts_with("TF_") {
+            cmd.env_remove(key);
+        }
+    }
+    cmd.current_dir(cwd)
+        .env("TFENV_ROOT", config)
+        .env("TFENV_CONFIG_DIR", config)
+        .env("TFENV_AUTO_INSTALL", "false")
+        .env("HOME", config.join("home"));
+    cmd
+}
+
+#[test]
+fn test_install_summary_and_exit_code() {
+    let tmp = tempfile::TempDir::new().unwrap();
+    let config = tmp.path();
+    let terraform = config.join("versions/1.5.7/terraform");
+    fs::create_dir_all(terraform.parent().unwrap()).unwrap();
+    fs::write(&terraform, "").unwrap();
+
+    let out = tfenv(config, config)
+        .args(["install", "1.5.7", "~> one"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(!out.status.success());
+    assert!(stdout.contains("1.5.7                1.5.7 already installed"));
+    assert!(stdout.contains("~> one               FAILED: invalid version constraint"));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("1 of 2 installs failed"));
+
+    let out = tfenv(config, config)
+        .args(["install", "1.5.7", "v1.5.7"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+}
+
+/// A fake installed binary printing its name, version and arguments.
+#[cfg(unix)]
+fn fake_install(versions_dir: &Path, version: &str, binary: &str) {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = versions_dir.join(version);
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join(binary);
+    fs::write(
+        &path,
+        format!("#!/bin/sh\necho \"{} {} $*\"\n", binary, version),
+    )
+    .unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn test_terragrunt_passes_flags_through() {
+    let tmp = tempfile::TempDir::new().unwrap();
+    let config = tmp.path();
+    fake_install(&config.join("versions"), "1.5.7", "terraform");
+    fake_install(&config.join("terragrunt/versions"), "0.55.1", "terragrunt");
+    fs::write(config.join(".terraform-version"), "1.5.7\n").unwrap();
+    fs::write(config.join(".terragrunt-version"), "0.55.1\n").unwrap();
+
+    let out = tfenv(config, config)
+        .args([
+            "terragrunt",
+            "run-all",
+            "plan",
+            "--terragrunt-non-interactive",
+        ])
+        .output()
+        .unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&out.stdout).trim(),
+        "terragrunt 0.55.1 run-all plan --terragrunt-non-interactive"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_exec_chdir_is_relative_to_the_process_directory() {
+    let tmp = tempfile::TempDir::new().unwrap();
+    let config = tmp.path();
+    fake_install(&config.join("versions"), "1.5.7", "terraform");
+    fake_install(&config.join("versions"), "1.6.0", "terraform");
+    let write = |path: &str, version: &str| {
+        let path = config.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, version).unwrap();
+    };
+    write("stacks/prod/.terraform-version", "1.5.7\n");
+    write("other/stacks/prod/.terraform-version", "1.6.0\n");
+    write("other/.terraform-version", "1.6.0\n");
+
+    // terraform runs in the process's directory and applies -chdir there
+    let out = tfenv(config, config)
+        .env("TFENV_DIR", "other")
+        .args(["exec", "-chdir=stacks/prod", "plan"])
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&out.stdout).trim(),
+        "terraform 1.5.7 -chdir=stacks/prod plan"
+    );
+    // without -chdir, TFENV_DIR selects the directory
+    let out = tfenv(config, config)
+        .env("TFENV_DIR", "other")
+        .args(["exec", "plan"])
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&out.stdout).trim(),
+        "terraform 1.6.0 plan"
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn test_parent_dir_in_chdir_and_tfenv_dir() {
+    let tmp = tempfile::TempDir::new().unwrap();
+    let config = tmp.path();
+    fake_install(&config.join("versions"), "1.5.7", "terraform");
+    fake_install(&config.join("versions"), "1.6.0", "terraform");
+    let dev = config.join("repo/stacks/dev");
+    fs::create_dir_all(&dev).unwrap();
+    fs::create_dir_all(config.join("repo/stacks/prod")).unwrap();
+    fs::write(dev.join(".terraform-version"), "1.5.7\n").unwrap();
+    fs::write(config.join("repo/.terraform-version"), "1.6.0\n").unwrap();
+
+    // ../prod is searched from repo/stacks/prod upwards, never through dev
+    let out = tfenv(config, &dev)
+        .args(["exec", "-chdir=../prod", "plan"])
+        .output()
+        .unwrap();
+    assert_eq!(
+        String::from_utf8_lossy(&out.stdout).trim(),
+        "terraform 1.6.0 -chdir=../prod plan"
+    );
+    let out = tfenv(config, &dev)
+        .env("TFENV_DIR", "../prod")
+        .args(["version", "--explain"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(out.status.success(), "{}", stdout);
+    assert!(stdout.contains("1.6.0"), "{}", stdout);
+    assert!(!stdout.contains("1.5.7"), "{}", stdout);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_exec_with_unreadable_state() {
+    let tmp = tempfile::TempDir::new().unwrap();
+    let config = tmp.path();
+    fake_install(&config.join("versions"), "1.5.7", "terraform");
+    fs::write(config.join(".terraform-version"), "1.5.7\n").unwrap();
+    fs::write(config.join("terraform.tfstate"), "").unwrap();
+
+    // the default check warns and runs terraform anyway
+    let out = tfenv(config, config)
+        .args(["exec", "plan"])
+        .output()
+        .unwrap();
+    assert!(out.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&out.stdout).trim(),
+        "terraform 1.5.7 plan"
+    );
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("warning: failed to parse"), "{}", stderr);
+
+    // refuse does not run what it cannot check
+    let out = tfenv(config, config)
+        .env("TFENV_STATE_CHECK", "refuse")
+        .args(["exec", "plan"])
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+    assert!(out.stdout.is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_recursive_install_of_a_mixed_tree() {
+    let tmp = tempfile::TempDir::new().unwrap();
+    let config = tmp.path();
+    fake_install(&config.join("versions"), "1.5.7", "terraform");
+    fake_install(&config.join("opentofu/versions"), "1.6.2", "tofu");
+    let stacks = config.join("stacks");
+    fs::create_dir_all(stacks.join("tf")).unwrap();
+    fs::create_dir_all(stacks.join("tofu")).unwrap();
+    fs::write(stacks.join("tf/.terraform-version"), "1.5.7\n").unwrap();
+    fs::write(stacks.join("tofu/.opentofu-version"), "1.6.2\n").unwrap();
+
+    let out = tfenv(config, config)
+        .args(["install", "--recursive", "stacks"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(out.status.success(), "{}", stdout);
+    assert!(stdout.contains(".terraform-version: 1.5.7 -> terraform 1.5.7"));
+    assert!(stdout.contains(".opentofu-version: 1.6.2 -> opentofu 1.6.2"));
+}
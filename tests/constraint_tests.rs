@@ -0,0 +1,206 @@
+use semver::Version;
+use tfenv_rs::constraint::{Constraints, Pick};
+
+fn versions(list: &[&str]) -> Vec<Version> {
+    list.iter().map(|v| Version::parse(v).unwrap()).collect()
+}
+
+fn allows(spec: &str, version: &str) -> bool {
+    Constraints::parse(spec)
+        .unwrap()
+        .matches(&Version::parse(version).unwrap())
+}
+
+#[test]
+fn test_constraint_grammar() {
+    assert!(allows("1.5.7", "1.5.7"));
+    assert!(allows("= 1.5", "1.5.0"));
+    assert!(!allows("!= 1.5.7", "1.5.7"));
+    assert!(allows(">= 1.3, < 1.6", "1.5.7"));
+    assert!(!allows(">= 1.3, < 1.6", "1.6.0"));
+    assert!(!allows("> 1.3", "1.3.0"));
+    assert!(allows("<= 1.3", "1.3.0"));
+
+    // ~> lets only the rightmost written segment grow
+    assert!(allows("~> 1.5.2", "1.5.9"));
+    assert!(!allows("~> 1.5.2", "1.5.1"));
+    assert!(!allows("~> 1.5.2", "1.6.0"));
+    assert!(allows("~> 1.5", "1.9.0"));
+    assert!(!allows("~> 1.5", "2.0.0"));
+    assert!(allows("~>1.5.0", "1.5.3"));
+
+    // prereleases only match terms naming a prerelease of the same version
+    assert!(!allows(">= 1.5.0", "1.6.0-beta1"));
+    assert!(allows(">= 1.6.0-alpha1", "1.6.0-beta1"));
+    assert!(!allows(">= 1.6.0-alpha1", "1.7.0-beta1"));
+    assert!(allows(">= 1.6.0-alpha1", "1.7.0"));
+    assert!(!allows("~> 1.6.0-beta1", "1.6.1"));
+
+    assert!(Constraints::parse(">= 1.x").is_err());
+    assert!(Constraints::parse("~> 1.2.3.4").is_err());
+}
+
+#[test]
+fn test_constraint_select() {
+    let candidates = versions(&["1.2.9", "1.3.0", "1.5.2", "1.5.7", "1.6.0-rc1", "1.6.6"]);
+    let c = Constraints::parse(">= 1.3, < 1.6").unwrap();
+    assert_eq!(
+        c.select(&candidates, Pick::Min).unwrap().to_string(),
+        "1.3.0"
+    );
+    assert_eq!(
+        c.select(&candidates, Pick::Max).unwrap().to_string(),
+        "1.5.7"
+    );
+    let c = Constraints::parse("~> 1.5.2").unwrap();
+    assert_eq!(
+        c.select(&candidates, Pick::Max).unwrap().to_string(),
+        "1.5.7"
+    );
+
+    let mut c = Constraints::parse(">= 1.6").unwrap();
+    c.intersect(Constraints::parse("< 1.4").unwrap());
+    let err = c.select(&candidates, Pick::Max).unwrap_err().to_string();
+    assert!(
+        err.contains("no version satisfies '>= 1.6, < 1.4'"),
+        "{}",
+        err
+    );
+    assert!(err.contains("'>= 1.6' allows 1 of 6 candidates"), "{}", err);
+    assert!(
+        err.contains("no candidate satisfies all of them"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn test_required_versions() {
+    use std::fs;
+    use tfenv_rs::product::Product;
+    use tfenv_rs::required::required_versions;
+
+    let dir = tempfile::tempdir().unwrap();
+    let write = |name: &str, body: &str| fs::write(dir.path().join(name), body).unwrap();
+    write(
+        "main.tf",
+        "# required_version = \"< 0.1\"\nterraform {\n  required_providers {}\n  required_version = \">= 1.3\"\n}\n",
+    );
+    write(
+        "versions.tf.json",
+        r#"{"terraform": [{"required_version": "< 1.6"}]}"#,
+    );
+    write(
+        "versions.tf",
+        "terraform { required_version = \"~> 1.4\" }\n",
+    );
+    write(
+        "versions.tofu",
+        "terraform { required_version = \"~> 1.8\" }\n",
+    );
+
+    let specs = |product: &str| -> Vec<(String, String)> {
+        required_versions(dir.path(), &Product::from_name(product).unwrap())
+            .unwrap()
+            .into_iter()
+            .map(|r| {
+                let file = r.file.file_name().unwrap().to_string_lossy().to_string();
+                (file, r.spec)
+            })
+            .collect()
+    };
+    let pairs = |list: &[(&str, &str)]| -> Vec<(String, String)> {
+        list.iter()
+            .map(|(f, s)| (f.to_string(), s.to_string()))
+            .collect()
+    };
+    assert_eq!(
+        specs("terraform"),
+        pairs(&[
+            ("main.tf", ">= 1.3"),
+            ("versions.tf", "~> 1.4"),
+            ("versions.tf.json", "< 1.6"),
+        ])
+    );
+    // versions.tofu hides versions.tf for OpenTofu
+    assert_eq!(
+        specs("opentofu"),
+        pairs(&[
+            ("main.tf", ">= 1.3"),
+            ("versions.tf.json", "< 1.6"),
+            ("versions.tofu", "~> 1.8"),
+        ])
+    );
+
+    write("broken.tf", "terraform {\n");
+    assert!(required_versions(dir.path(), &Product::from_name("terraform").unwrap()).is_err());
+}
+
+#[test]
+fn test_configuration_required_versions_follow_modules() {
+    use std::fs;
+    use tfenv_rs::product::Product;
+    use tfenv_rs::required::configuration_required_versions;
+
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("stack");
+    let write = |path: &str, body: &str| {
+        let path = dir.path().join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, body).unwrap();
+    };
+    write(
+        "stack/main.tf",
+        "terraform { required_version = \">= 1.3\" }\nmodule \"net\" { source = \"./modules/net\" }\nmodule \"vpc\" { source = \"terraform-aws-modules/vpc/aws\" }\n",
+    );
+    // local modules are followed recursively, including back-references
+    write(
+        "stack/modules/net/main.tf",
+        "terraform { required_version = \"< 1.6\" }\nmodule \"shared\" { source = \"../../../shared\" }\n",
+    );
+    write(
+        "shared/main.tf.json",
+        r#"{"terraform": {"required_version": "!= 1.5.0"}, "module": {"net": {"source": "../stack/modules/net"}}}"#,
+    );
+    write(
+        "stack/.terraform/modules/modules.json",
+        r#"{"Modules": [{"Key": "", "Source": "", "Dir": "."}, {"Key": "vpc", "Source": "registry.terraform.io/terraform-aws-modules/vpc/aws", "Dir": ".terraform/modules/vpc"}]}"#,
+    );
+    write(
+        "stack/.terraform/modules/vpc/versions.tf",
+        "terraform { required_version = \">= 1.0\" }\n",
+    );
+
+    let mut specs: Vec<String> =
+        configuration_required_versions(&root, &Product::from_name("terraform").unwrap())
+            .unwrap()
+            .into_iter()
+            .map(|r| r.spec)
+            .collect();
+    specs.sort();
+    assert_eq!(specs, vec!["!= 1.5.0", "< 1.6", ">= 1.0", ">= 1.3"]);
+}
+
+#[test]
+fn test_constraint_expression_detection() {
+    use tfenv_rs::constraint::is_constraint_expression;
+
+    for spec in [
+        "~> 1.5",
+        ">= 1.5, < 1.7",
+        "!= 1.6.0",
+        "= 1.5.7",
+        "1.5.0, != 1.5.3",
+    ] {
+        assert!(is_constraint_expression(spec), "{}", spec);
+    }
+    for spec in [
+        "1.5.7",
+        "latest",
+        "latest:^1\\.5\\.",
+        "latest:^1\\.[5,6]",
+        "min-required",
+    ] {
+        assert!(!is_constraint_expression(spec), "{}", spec);
+    }
+}
@@ -1,3 +1,6 @@
+// the pre-`Product` helpers are kept for library users
+#![allow(deprecated)]
+
 #[test]
 fn test_asset_name() {
     use tfenv_rs::installer::asset_name;
@@ -14,3 +17,315 @@ fn test_map_arch_and_os() {
     assert!(!map_os().is_empty());
     assert!(!map_arch().is_empty());
 }
+
+#[test]
+fn test_parse_archive_name() {
+    use tfenv_rs::installer::parse_archive_name;
+    assert_eq!(
+        parse_archive_name("terraform_1.6.3_linux_amd64.zip"),
+        Some(("terraform".to_string(), "1.6.3".to_string()))
+    );
+    assert_eq!(
+        parse_archive_name("tofu_1.7.0-rc1_darwin_arm64.zip"),
+        Some(("tofu".to_string(), "1.7.0-rc1".to_string()))
+    );
+    assert_eq!(parse_archive_name("terraform.zip"), None);
+}
+
+#[test]
+fn test_parse_sha256sums() {
+    use tfenv_rs::installer::parse_sha256sums;
+    let a = "a".repeat(64);
+    let b = "B".repeat(64);
+    let body = format!(
+        "{}  terraform_1.6.3_linux_amd64.zip\n{} *terraform_1.6.3_linux_amd64.zip.sig\n\n",
+        a, b
+    );
+    let sums = parse_sha256sums(&body).unwrap();
+    assert_eq!(sums.len(), 2);
+    assert_eq!(sums["terraform_1.6.3_linux_amd64.zip"], a);
+    assert_eq!(sums["terraform_1.6.3_linux_amd64.zip.sig"], "b".repeat(64));
+
+    // exact file-name match only: a suffix of another entry is not found
+    assert!(!sums.contains_key("linux_amd64.zip"));
+
+    let dup = format!("{} x.zip\n{} x.zip\n", a, a);
+    assert!(parse_sha256sums(&dup).is_err());
+    assert!(parse_sha256sums("nothex  x.zip\n").is_err());
+    assert!(parse_sha256sums(&a).is_err());
+}
+
+#[test]
+fn test_asset_name_for_other_platform() {
+    use tfenv_rs::archive::ArchiveFormat;
+    use tfenv_rs::backend::release_asset_name;
+    use tfenv_rs::product::Product;
+    assert_eq!(
+        Product::from_name("terraform")
+            .unwrap()
+            .asset_name("1.6.3", "linux", "arm64"),
+        "terraform_1.6.3_linux_arm64.zip"
+    );
+    assert_eq!(
+        release_asset_name("tofu", "1.6.2", "darwin", "amd64", ArchiveFormat::TarGz),
+        "tofu_1.6.2_darwin_amd64.tar.gz"
+    );
+}
+
+#[test]
+fn test_platform_table() {
+    use tfenv_rs::installer::{arch_name, os_name};
+    assert_eq!(os_name("macos"), Some("darwin"));
+    assert_eq!(os_name("darwin"), Some("darwin"));
+    assert_eq!(os_name("illumos"), Some("solaris"));
+    assert_eq!(os_name("FreeBSD"), Some("freebsd"));
+    assert_eq!(os_name("plan9"), None);
+    assert_eq!(arch_name("x86"), Some("386"));
+    assert_eq!(arch_name("386"), Some("386"));
+    assert_eq!(arch_name("aarch64"), Some("arm64"));
+    assert_eq!(arch_name("arm"), Some("arm"));
+    assert_eq!(arch_name("powerpc64"), Some("ppc64le"));
+    assert_eq!(arch_name("s390x"), Some("s390x"));
+    assert_eq!(arch_name("mips"), None);
+}
+
+#[test]
+fn test_parse_plain_version_output() {
+    use tfenv_rs::installer::parse_plain_version_output;
+    assert_eq!(
+        parse_plain_version_output("Terraform v0.12.31\n\nYour version is out of date"),
+        Some("0.12.31".to_string())
+    );
+    assert_eq!(
+        parse_plain_version_output("terragrunt version v0.55.1-beta2\n"),
+        Some("0.55.1-beta2".to_string())
+    );
+    // only the first line is read
+    assert_eq!(parse_plain_version_output("Packer\nv1.9.4\n"), None);
+    assert_eq!(parse_plain_version_output(""), None);
+}
+
+/// Install `script` as a raw-binary archive of `product` `version` into `config`.
+#[cfg(unix)]
+fn install_script(
+    config: &std::path::Path,
+    product: &str,
+    version: &str,
+    script: &str,
+) -> anyhow::Result<()> {
+    let archive = config.join(format!("{}-{}", product, version));
+    std::fs::write(&archive, script).unwrap();
+    tfenv_rs::installer::install_from_archive(
+        config,
+        config,
+        &archive,
+        Some(product),
+        Some(version),
+        None,
+        &[],
+    )
+}
+
+/// Nothing of a failed install may remain: no version directory, no staging directory.
+#[cfg(unix)]
+fn assert_rolled_back(versions_dir: &std::path::Path) {
+    let left: Vec<String> = std::fs::read_dir(versions_dir)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+        .collect();
+    assert!(left.is_empty(), "left behind: {:?}", left);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_install_smoke_test() {
+    let tmp = tempfile::TempDir::new().unwrap();
+    let config = tmp.path();
+
+    // Terraform is asked for `version -json`
+    install_script(
+        config,
+        "terraform",
+        "1.5.7",
+        "#!/bin/sh\necho '{\"terraform_version\": \"1.5.7\"}'\n",
+    )
+    .unwrap();
+    assert!(config.join("versions/1.5.7/terraform").exists());
+    assert!(config.join("versions/1.5.7/manifest.json").exists());
+    // other products print plain text
+    install_script(
+        config,
+        "packer",
+        "1.9.4",
+        "#!/bin/sh\necho 'Packer v1.9.4'\n",
+    )
+    .unwrap();
+    assert!(config.join("packer/versions/1.9.4/packer").exists());
+
+    // a binary reporting another version is rolled back
+    let err = install_script(
+        config,
+        "vault",
+        "1.15.0",
+        "#!/bin/sh\necho 'Vault v1.14.0'\n",
+    )
+    .unwrap_err();
+    assert!(format!("{:#}", err).contains("reports version 1.14.0"));
+    assert_rolled_back(&config.join("vault/versions"));
+
+    // so is one that cannot be executed
+    let err = install_script(config, "consul", "1.17.0", "\u{7f}ELF garbage").unwrap_err();
+    assert!(format!("{:#}", err).contains("failed to execute"));
+    assert_rolled_back(&config.join("consul/versions"));
+
+    // and one that fails
+    let err = install_script(config, "nomad", "1.7.0", "#!/bin/sh\nexit 3\n").unwrap_err();
+    assert!(format!("{:#}", err).contains("exited with"));
+    assert_rolled_back(&config.join("nomad/versions"));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_install_from_archive_checksums() {
+    use sha2::{Digest, Sha256};
+    use std::fs;
+    use std::io::Write;
+    use tfenv_rs::installer::install_from_archive;
+
+    let tmp = tempfile::TempDir::new().unwrap();
+    let config = tmp.path().join("config");
+    let downloads = tmp.path().join("downloads");
+    fs::create_dir_all(&downloads).unwrap();
+    let name = "terraform_1.5.7_linux_amd64.zip";
+    let archive = downloads.join(name);
+    let mut zip = zip::ZipWriter::new(fs::File::create(&archive).unwrap());
+    zip.start_file("terraform", zip::write::FileOptions::default())
+        .unwrap();
+    zip.write_all(b"#!/bin/sh\necho '{\"terraform_version\": \"1.5.7\"}'\n")
+        .unwrap();
+    zip.finish().unwrap();
+    let actual = hex::encode(Sha256::digest(fs::read(&archive).unwrap()));
+    let sums = |hash: &str| format!("{}  {}\n", hash, name);
+    let install = |sums: Option<&std::path::Path>| {
+        install_from_archive(&config, &config, &archive, None, None, sums, &[])
+    };
+    let installed = config.join("versions/1.5.7/terraform");
+
+    // the product's SHA256SUMS next to the archive is found and checked
+    let beside = downloads.join("terraform_1.5.7_SHA256SUMS");
+    fs::write(&beside, sums(&"0".repeat(64))).unwrap();
+    let err = install(None).unwrap_err();
+    assert!(format!("{:#}", err).contains("SHA256 mismatch"));
+    assert!(!installed.exists());
+
+    // an explicit SUMS file is used instead
+    let explicit = tmp.path().join("SHA256SUMS");
+    fs::write(&explicit, sums(&actual)).unwrap();
+    install(Some(&explicit)).unwrap();
+    assert!(installed.exists());
+    fs::remove_dir_all(config.join("versions/1.5.7")).unwrap();
+
+    // and has to list the archive
+    fs::write(&explicit, sums(&actual).replace(name, "other.zip")).unwrap();
+    let err = install(Some(&explicit)).unwrap_err();
+    assert!(format!("{:#}", err).contains("No checksum found"));
+    assert!(!installed.exists());
+
+    fs::write(&beside, sums(&actual)).unwrap();
+    install(None).unwrap();
+    assert!(installed.exists());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_pinned_checksum_of_installed_version() {
+    use sha2::{Digest, Sha256};
+    use tfenv_rs::installer::{install_version, InstallStatus};
+    use tfenv_rs::product::Product;
+
+    let tmp = tempfile::TempDir::new().unwrap();
+    let config = tmp.path();
+    let script = "#!/bin/sh\necho '{\"terraform_version\": \"1.5.7\"}'\n";
+    install_script(config, "terraform", "1.5.7", script).unwrap();
+    let terraform = Product::from_name("terraform").unwrap();
+    let actual = hex::encode(Sha256::digest(script.as_bytes()));
+
+    // nothing is downloaded: the manifest records the archive's hash
+    assert_eq!(
+        install_version(config, config, &terraform, Some("1.5.7"), &[actual]).unwrap(),
+        InstallStatus::AlreadyInstalled
+    );
+    let err =
+        install_version(config, config, &terraform, Some("1.5.7"), &["0".repeat(64)]).unwrap_err();
+    assert!(format!("{:#}", err).contains("SHA256 mismatch"));
+
+    std::fs::remove_file(config.join("versions/1.5.7/manifest.json")).unwrap();
+    assert!(install_version(config, config, &terraform, Some("1.5.7"), &["0".repeat(64)]).is_err());
+    // without a pin, a version installed without a manifest is still accepted
+    assert!(install_version(config, config, &terraform, Some("1.5.7"), &[]).is_ok());
+}
+
+#[test]
+fn test_install_lock() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+    use tfenv_rs::installer::InstallLock;
+
+    let tmp = tempfile::TempDir::new().unwrap();
+    let versions = tmp.path().to_path_buf();
+    let lock_file = versions.join(".1.5.7.lock");
+
+    // a second install of the same version waits for the first
+    let first = InstallLock::acquire(&versions, "1.5.7").unwrap();
+    assert!(lock_file.exists());
+    let acquired = Arc::new(AtomicBool::new(false));
+    let waiter = {
+        let (versions, acquired) = (versions.clone(), acquired.clone());
+        std::thread::spawn(move || {
+            let _second = InstallLock::acquire(&versions, "1.5.7").unwrap();
+            acquired.store(true, Ordering::SeqCst);
+        })
+    };
+    // other versions are not blocked
+    drop(InstallLock::acquire(&versions, "1.6.0").unwrap());
+    std::thread::sleep(Duration::from_millis(500));
+    assert!(!acquired.load(Ordering::SeqCst));
+    drop(first);
+    waiter.join().unwrap();
+    assert!(acquired.load(Ordering::SeqCst));
+    assert!(!lock_file.exists());
+
+    // a lock left behind by a killed process is taken over once stale
+    let stale = std::fs::File::create(&lock_file).unwrap();
+    stale
+        .set_modified(SystemTime::now() - Duration::from_secs(3600))
+        .unwrap();
+    drop(InstallLock::acquire(&versions, "1.5.7").unwrap());
+    assert!(!lock_file.exists());
+
+    // however many waiters find it stale, one holds the lock at a time
+    let stale = std::fs::File::create(&lock_file).unwrap();
+    stale
+        .set_modified(SystemTime::now() - Duration::from_secs(3600))
+        .unwrap();
+    let holders = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let start = Arc::new(std::sync::Barrier::new(8));
+    let waiters: Vec<_> = (0..8)
+        .map(|_| {
+            let (versions, holders, start) = (versions.clone(), holders.clone(), start.clone());
+            std::thread::spawn(move || {
+                start.wait();
+                let _lock = InstallLock::acquire(&versions, "1.5.7").unwrap();
+                assert_eq!(holders.fetch_add(1, Ordering::SeqCst), 0);
+                std::thread::sleep(Duration::from_millis(50));
+                holders.fetch_sub(1, Ordering::SeqCst);
+            })
+        })
+        .collect();
+    for waiter in waiters {
+        waiter.join().unwrap();
+    }
+    let left: Vec<_> = std::fs::read_dir(&versions).unwrap().collect();
+    assert!(left.is_empty());
+}
@@ -0,0 +1,285 @@
+use std::path::Path;
+use tfenv_rs::backend::SignatureScheme;
+use tfenv_rs::product::Product;
+
+#[test]
+fn test_builtin_and_hashicorp_products() {
+    let tf = Product::from_name("terraform").unwrap();
+    assert_eq!(tf.signature_scheme(), SignatureScheme::GpgDetached);
+    assert_eq!(tf.version_file(), ".terraform-version");
+    assert_eq!(tf.version_env(), "TFENV_TERRAFORM_VERSION");
+    assert_eq!(
+        tf.versions_dir(Path::new("/cfg")),
+        Path::new("/cfg/versions")
+    );
+
+    let tofu = Product::from_name("tofu").unwrap();
+    assert_eq!(tofu.name(), "opentofu");
+    assert_eq!(tofu.binary(), "tofu");
+    assert_eq!(
+        tofu.sha256sums_url("1.6.0").as_deref(),
+        Some("https://github.com/opentofu/opentofu/releases/download/v1.6.0/tofu_1.6.0_SHA256SUMS")
+    );
+
+    let ls = Product::from_name("Terraform-LS").unwrap();
+    assert_eq!(ls.name(), "terraform-ls");
+    assert_eq!(ls.version_file(), ".terraform-ls-version");
+    assert_eq!(ls.version_env(), "TFENV_TERRAFORM_LS_VERSION");
+    assert_eq!(
+        ls.versions_dir(Path::new("/cfg")),
+        Path::new("/cfg/terraform-ls/versions")
+    );
+    assert_eq!(
+        ls.default_version_path(Path::new("/cfg")),
+        Path::new("/cfg/terraform-ls/version")
+    );
+    assert_eq!(ls.version_args(), ["version"]);
+
+    assert!(Product::from_name("../vault").is_err());
+    assert!(Product::from_name("").is_err());
+}
+
+#[test]
+fn test_parse_listing() {
+    let packer = Product::from_name("packer").unwrap();
+    let body = r#"<ul>
+        <li><a href="/packer/1.9.4/">packer_1.9.4</a></li>
+        <li><a href="/packer/1.10.0/">packer_1.10.0</a></li>
+        <li><a href="/vault/1.15.0/">vault_1.15.0</a></li>
+        <li><a href="/packer/1.10.0-beta1/">packer_1.10.0-beta1</a></li>
+    </ul>"#;
+    let versions: Vec<String> = packer
+        .parse_listing(body)
+        .iter()
+        .map(|v| v.to_string())
+        .collect();
+    assert_eq!(versions, ["1.10.0", "1.10.0-beta1", "1.9.4"]);
+}
+
+#[test]
+fn test_terragrunt_assets() {
+    let tg = Product::from_name("terragrunt").unwrap();
+    assert_eq!(tg.version_file(), ".terragrunt-version");
+    assert_eq!(
+        tg.asset_name("0.54.0", "linux", "amd64"),
+        "terragrunt_linux_amd64"
+    );
+    assert_eq!(
+        tg.asset_name("0.54.0", "windows", "amd64"),
+        "terragrunt_windows_amd64.exe"
+    );
+    assert_eq!(
+        tg.asset_platform("0.54.0", "terragrunt_windows_386.exe"),
+        Some("windows_386".to_string())
+    );
+    assert!(tg
+        .sha256sums_url("0.54.0")
+        .unwrap()
+        .ends_with("/v0.54.0/SHA256SUMS"));
+    assert_eq!(tg.signature_scheme(), SignatureScheme::None);
+    assert!(tg.is_constraint_file("terragrunt.hcl"));
+    assert!(!tg.is_constraint_file("main.tf"));
+    assert_eq!(tg.constraint_attribute(), "terragrunt_version_constraint");
+}
+
+#[test]
+fn test_register_backend() {
+    use semver::Version;
+    use tfenv_rs::backend::ProductBackend;
+    use tfenv_rs::product::register_backend;
+
+    struct Fork;
+    impl ProductBackend for Fork {
+        fn name(&self) -> &str {
+            "terraform-fork"
+        }
+        fn binary(&self) -> &str {
+            "terraform"
+        }
+        fn listing_url(&self) -> String {
+            "https://artifacts.example.com/terraform-fork/".to_string()
+        }
+        fn parse_listing(&self, _body: &str) -> Vec<Version> {
+            vec![Version::new(1, 6, 3)]
+        }
+        fn asset_name(&self, version: &str, os: &str, arch: &str) -> String {
+            format!("terraform-fork-{}-{}-{}.zip", version, os, arch)
+        }
+        fn asset_url(&self, version: &str, asset: &str) -> String {
+            format!(
+                "https://artifacts.example.com/terraform-fork/{}/{}",
+                version, asset
+            )
+        }
+        fn asset_platform(&self, _version: &str, _asset: &str) -> Option<String> {
+            None
+        }
+    }
+
+    register_backend(Fork);
+    let fork = Product::from_name("terraform-fork").unwrap();
+    assert_eq!(
+        fork.binary_name(),
+        if cfg!(windows) {
+            "terraform.exe"
+        } else {
+            "terraform"
+        }
+    );
+    assert_eq!(
+        fork.asset_url("1.6.3", &fork.asset_name("1.6.3", "linux", "amd64")),
+        "https://artifacts.example.com/terraform-fork/1.6.3/terraform-fork-1.6.3-linux-amd64.zip"
+    );
+    assert_eq!(fork.version_env(), "TFENV_TERRAFORM_FORK_VERSION");
+    assert_eq!(fork.sha256sums_url("1.6.3"), None);
+}
+
+#[test]
+fn test_custom_products_file() {
+    use tfenv_rs::archive::ArchiveFormat;
+    use tfenv_rs::backend::ProductBackend;
+    use tfenv_rs::custom::parse_custom_products;
+
+    let products = parse_custom_products(
+        r#"{ "products": [
+            {
+                "name": "tflint",
+                "listing_url": "https://api.github.com/repos/terraform-linters/tflint/releases",
+                "listing_json_path": "[*].tag_name",
+                "asset": "tflint_{os}_{arch}.zip",
+                "asset_url": "https://github.com/terraform-linters/tflint/releases/download/v{version}/{asset}",
+                "checksums_url": "https://github.com/terraform-linters/tflint/releases/download/v{version}/checksums.txt",
+                "version_args": ["--version"]
+            },
+            {
+                "name": "deployctl",
+                "version_file": ".deployctl",
+                "listing_url": "https://artifacts.example.com/deployctl/",
+                "listing_regex": "deployctl-(?P<version>[0-9.]+)-",
+                "asset": "deployctl-{version}-{os}-{arch}{exe}",
+                "asset_url": "https://artifacts.example.com/deployctl/{asset}"
+            },
+            {
+                "name": "bundle",
+                "listing_url": "https://artifacts.example.com/bundle/",
+                "asset": "bundle-{version}-{os}-{arch}",
+                "asset_url": "https://artifacts.example.com/bundle/{asset}",
+                "checksums_url": "https://artifacts.example.com/bundle/{version}/{os}_{arch}/{asset}.sha256",
+                "archive_format": "tar.gz"
+            }
+        ] }"#,
+    )
+    .unwrap();
+    let tflint = &products[0];
+    assert_eq!(tflint.archive_format(), ArchiveFormat::Zip);
+    assert_eq!(
+        tflint.asset_url("0.50.0", &tflint.asset_name("0.50.0", "darwin", "arm64")),
+        "https://github.com/terraform-linters/tflint/releases/download/v0.50.0/tflint_darwin_arm64.zip"
+    );
+    assert_eq!(
+        tflint.sha256sums_file("0.50.0").as_deref(),
+        Some("checksums.txt")
+    );
+    assert_eq!(
+        tflint
+            .asset_platform("0.50.0", "tflint_linux_386.zip")
+            .as_deref(),
+        Some("linux_386")
+    );
+    let listing = r#"[{"tag_name": "v0.49.0"}, {"tag_name": "v0.50.1"}, {"tag_name": "nightly"}]"#;
+    let versions: Vec<String> = tflint
+        .parse_listing(listing)
+        .iter()
+        .map(|v| v.to_string())
+        .collect();
+    assert_eq!(versions, ["0.50.1", "0.49.0"]);
+    assert_eq!(tflint.version_args(), ["--version"]);
+
+    let deployctl = &products[1];
+    assert_eq!(deployctl.archive_format(), ArchiveFormat::Binary);
+    assert_eq!(deployctl.version_file(), ".deployctl");
+    assert_eq!(
+        deployctl.asset_name("2.1.0", "windows", "amd64"),
+        "deployctl-2.1.0-windows-amd64.exe"
+    );
+    let versions: Vec<String> = deployctl
+        .parse_listing(r#"<a href="deployctl-2.0.0-linux-amd64">x</a> deployctl-2.1.0-linux-amd64"#)
+        .iter()
+        .map(|v| v.to_string())
+        .collect();
+    assert_eq!(versions, ["2.1.0", "2.0.0"]);
+    assert_eq!(deployctl.sha256sums_url("2.1.0"), None);
+
+    // an explicit format wins over the asset's (missing) extension
+    let bundle = &products[2];
+    assert_eq!(bundle.archive_format(), ArchiveFormat::TarGz);
+    assert_eq!(
+        bundle.asset_name("1.0.0", "linux", "amd64"),
+        "bundle-1.0.0-linux-amd64"
+    );
+    // a checksum file per asset
+    assert_eq!(bundle.sha256sums_url("1.0.0"), None);
+    assert_eq!(
+        bundle
+            .asset_sha256sums_url("1.0.0", "bundle-1.0.0-linux-amd64")
+            .as_deref(),
+        Some("https://artifacts.example.com/bundle/1.0.0/linux_amd64/bundle-1.0.0-linux-amd64.sha256")
+    );
+    assert_eq!(
+        tflint.asset_sha256sums_url("0.50.0", "tflint_linux_amd64.zip"),
+        tflint.sha256sums_url("0.50.0")
+    );
+
+    assert!(parse_custom_products(r#"{ "products": [ { "name": "x" } ] }"#).is_err());
+    // products are looked up by their lowercased name
+    let err = parse_custom_products(
+        r#"{ "products": [ {
+            "name": "TFLint",
+            "listing_url": "https://example.com/",
+            "asset": "tflint",
+            "asset_url": "https://example.com/{asset}"
+        } ] }"#,
+    )
+    .unwrap_err();
+    assert!(err.to_string().contains("invalid product name 'TFLint'"));
+}
+
+#[test]
+fn test_detect_product() {
+    use std::fs;
+    use tfenv_rs::product::detect_product;
+    let tmp = tempfile::TempDir::new().unwrap();
+    let root = tmp.path();
+    for dir in ["tofu/tf", "tofu/mod", "files/deep", "registry"] {
+        fs::create_dir_all(root.join(dir)).unwrap();
+    }
+    fs::write(root.join("tofu/.opentofu-version"), "1.6.0\n").unwrap();
+    fs::write(root.join("tofu/tf/.terraform-version"), "1.5.7\n").unwrap();
+    fs::write(root.join("files/main.tofu"), "").unwrap();
+    fs::write(root.join("files/.terraform-version"), "1.6.0\n").unwrap();
+    fs::write(
+        root.join("registry/versions.tf"),
+        "terraform {\n  required_providers {\n    aws = {\n      source = \"registry.opentofu.org/hashicorp/aws\"\n    }\n  }\n}\n",
+    )
+    .unwrap();
+
+    let detected = |dir: &str| detect_product(&root.join(dir)).map(|(name, path, _)| (name, path));
+    // nearest marker wins
+    assert_eq!(
+        detected("tofu/mod"),
+        Some(("opentofu", root.join("tofu/.opentofu-version")))
+    );
+    assert_eq!(
+        detected("tofu/tf"),
+        Some(("terraform", root.join("tofu/tf/.terraform-version")))
+    );
+    // *.tofu files beat a .terraform-version in the same directory
+    assert_eq!(
+        detected("files/deep"),
+        Some(("opentofu", root.join("files/main.tofu")))
+    );
+    assert_eq!(
+        detected("registry"),
+        Some(("opentofu", root.join("registry/versions.tf")))
+    );
+}
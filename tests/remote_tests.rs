@@ -0,0 +1,54 @@
+use std::env;
+use tfenv_rs::product::Product;
+
+// a single test: it changes the environment of the whole test binary
+#[test]
+fn test_remote_overrides_are_per_product() {
+    let terraform = Product::from_name("terraform").unwrap();
+    let terragrunt = Product::from_name("terragrunt").unwrap();
+    let opentofu = Product::from_name("opentofu").unwrap();
+    let asset = |p: &Product| p.asset_url("1.0.0", "asset");
+
+    // TFENV_REMOTE is Terraform's mirror only
+    env::set_var("TFENV_REMOTE", "https://mirror.example/terraform");
+    assert_eq!(terraform.listing_url(), "https://mirror.example/terraform");
+    assert_eq!(
+        asset(&terraform),
+        "https://mirror.example/terraform/1.0.0/asset"
+    );
+    assert_eq!(
+        terragrunt.listing_url(),
+        "https://github.com/gruntwork-io/terragrunt/releases"
+    );
+    assert_eq!(
+        asset(&terragrunt),
+        "https://github.com/gruntwork-io/terragrunt/releases/download/v1.0.0/asset"
+    );
+    assert_eq!(
+        opentofu.listing_url(),
+        "https://github.com/opentofu/opentofu/releases"
+    );
+
+    // every product has its own
+    env::set_var(
+        "TFENV_TERRAGRUNT_REMOTE",
+        "https://mirror.example/terragrunt/",
+    );
+    env::set_var("TFENV_OPENTOFU_REMOTE", "https://mirror.example/tofu/");
+    env::set_var("TFENV_TERRAFORM_REMOTE", "https://mirror.example/tf/");
+    assert_eq!(
+        asset(&terragrunt),
+        "https://mirror.example/terragrunt/v1.0.0/asset"
+    );
+    assert_eq!(asset(&opentofu), "https://mirror.example/tofu/v1.0.0/asset");
+    assert_eq!(asset(&terraform), "https://mirror.example/tf/1.0.0/asset");
+
+    for var in [
+        "TFENV_REMOTE",
+        "TFENV_TERRAGRUNT_REMOTE",
+        "TFENV_OPENTOFU_REMOTE",
+        "TFENV_TERRAFORM_REMOTE",
+    ] {
+        env::remove_var(var);
+    }
+}
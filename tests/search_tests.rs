@@ -0,0 +1,38 @@
+use std::env;
+use std::fs;
+use tfenv_rs::search::{search_dirs, SearchStop};
+
+// a single test: it changes the environment of the whole test binary
+#[test]
+fn test_search_boundaries() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().canonicalize().unwrap();
+    let repo = root.join("repo");
+    let start = repo.join("stacks").join("prod");
+    fs::create_dir_all(&start).unwrap();
+    fs::create_dir_all(repo.join(".git")).unwrap();
+
+    env::remove_var("TFENV_CEILING_DIRECTORIES");
+    env::remove_var("TFENV_STOP_AT_GIT_ROOT");
+    let (dirs, stop) = search_dirs(&start);
+    assert_eq!(dirs.first(), Some(&start));
+    assert!(dirs.contains(&root));
+    assert!(matches!(stop, SearchStop::Root(_)));
+
+    env::set_var("TFENV_STOP_AT_GIT_ROOT", "true");
+    let (dirs, stop) = search_dirs(&start);
+    assert_eq!(dirs, vec![start.clone(), repo.join("stacks"), repo.clone()]);
+    assert_eq!(stop, SearchStop::GitRoot(repo.clone()));
+    env::remove_var("TFENV_STOP_AT_GIT_ROOT");
+
+    // ceilings are never entered; relative entries are ignored
+    let ceilings = env::join_paths([repo.join("stacks"), "relative".into()]).unwrap();
+    env::set_var("TFENV_CEILING_DIRECTORIES", &ceilings);
+    let (dirs, stop) = search_dirs(&start);
+    assert_eq!(dirs, vec![start.clone()]);
+    assert_eq!(stop, SearchStop::Ceiling(repo.join("stacks")));
+    // but a search may start in one
+    let (dirs, _) = search_dirs(&repo.join("stacks"));
+    assert_eq!(dirs.first(), Some(&repo.join("stacks")));
+    env::remove_var("TFENV_CEILING_DIRECTORIES");
+}
@@ -0,0 +1,128 @@
+use std::env;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use tfenv_rs::product::Product;
+use tfenv_rs::trace::Trace;
+use tfenv_rs::version::resolve_version_traced;
+
+/// Serve `body` to every request on a loopback port; returns the base URL.
+fn serve(body: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut request = [0u8; 4096];
+            let _ = stream.read(&mut request);
+            let _ = write!(
+                stream,
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+        }
+    });
+    url
+}
+
+/// `(step, path relative to root, detail)` of each recorded step.
+fn steps(trace: &Trace, root: &Path) -> Vec<(String, String, String)> {
+    trace
+        .steps
+        .iter()
+        .map(|s| {
+            let path = s
+                .path
+                .as_ref()
+                .map(|p| p.strip_prefix(root).unwrap_or(p).display().to_string())
+                .unwrap_or_default();
+            (s.step.to_string(), path, s.detail.clone())
+        })
+        .collect()
+}
+
+// a single test: it changes the environment of the whole test binary
+#[test]
+fn test_resolution_records_each_step() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().canonicalize().unwrap();
+    let config = root.join("config");
+    let start = root.join("work").join("sub");
+    fs::create_dir_all(&start).unwrap();
+    for v in ["1.5.2", "1.5.7", "1.6.0"] {
+        fs::create_dir_all(config.join("versions").join(v)).unwrap();
+    }
+    fs::write(root.join("work/.terraform-version"), "~> 1.5.0\n").unwrap();
+    for var in [
+        "TFENV_TERRAFORM_VERSION",
+        "TFENV_TOOL_VERSIONS",
+        "TFENV_STOP_AT_GIT_ROOT",
+        "TF_WORKSPACE",
+        "TF_DATA_DIR",
+    ] {
+        env::remove_var(var);
+    }
+    env::set_var("TFENV_CEILING_DIRECTORIES", &root);
+    env::set_var("NO_PROXY", "127.0.0.1");
+    let terraform = Product::from_name("terraform").unwrap();
+
+    // an installed version satisfies the constraint: the remote listing is not needed
+    let mut trace = Trace::default();
+    let resolved = resolve_version_traced(&root, &config, &terraform, &start, &mut trace).unwrap();
+    assert_eq!(resolved.version, "1.5.7");
+    let expected = [
+        ("start", "work/sub", "resolving for this directory"),
+        ("env", "", "TFENV_TERRAFORM_VERSION is not set"),
+        ("workspace", "", "none selected"),
+        ("version-file", "work/sub/.terraform-version", "not found"),
+        ("version-file", "work/sub/.tool-versions", "not found"),
+        ("version-file", "work/.terraform-version", "wins"),
+        ("spec", "", "requested '~> 1.5.0'"),
+        (
+            "expand",
+            "",
+            "constraint expression '~> 1.5.0', newest match wins",
+        ),
+        (
+            "local",
+            "config/versions",
+            "installed: 1.5.2, 1.5.7, 1.6.0; matching: 1.5.2, 1.5.7",
+        ),
+        ("result", "", "1.5.7"),
+    ];
+    let expected: Vec<(String, String, String)> = expected
+        .iter()
+        .map(|(a, b, c)| (a.to_string(), b.to_string(), c.to_string()))
+        .collect();
+    assert_eq!(steps(&trace, &root), expected);
+
+    // nothing installed matches: the remote listing is consulted, unless auto-install is off
+    fs::write(root.join("work/.terraform-version"), "~> 1.7.0\n").unwrap();
+    env::set_var("TFENV_AUTO_INSTALL", "false");
+    let mut trace = Trace::default();
+    assert!(resolve_version_traced(&root, &config, &terraform, &start, &mut trace).is_err());
+    let last = steps(&trace, &root).pop().unwrap();
+    assert_eq!(last.0, "remote");
+    assert_eq!(last.2, "not consulted: auto-install disabled");
+
+    let url =
+        serve(r#"<a href="/terraform/1.7.1/">1.7.1</a><a href="/terraform/1.7.0/">1.7.0</a>"#);
+    env::set_var("TFENV_REMOTE", &url);
+    env::set_var("TFENV_AUTO_INSTALL", "true");
+    let mut trace = Trace::default();
+    let resolved = resolve_version_traced(&root, &config, &terraform, &start, &mut trace).unwrap();
+    assert_eq!(resolved.version, "1.7.1");
+    let found = steps(&trace, &root);
+    assert!(found.contains(&(
+        "remote".to_string(),
+        String::new(),
+        format!("consulted {}: 2 versions", url)
+    )));
+    assert_eq!(found.last().unwrap().2, "1.7.1");
+
+    env::remove_var("TFENV_REMOTE");
+    env::remove_var("TFENV_AUTO_INSTALL");
+    env::remove_var("TFENV_CEILING_DIRECTORIES");
+}
@@ -0,0 +1,388 @@
+#[test]
+fn test_parse_version_spec_with_pins() {
+    use tfenv_rs::version::parse_version_spec;
+    let a = "a".repeat(64);
+    let b = "B".repeat(64);
+    let spec = parse_version_spec(&format!("1.6.3 sha256:{} sha256:{}", a, b)).unwrap();
+    assert_eq!(spec.spec, "1.6.3");
+    assert_eq!(spec.sha256, vec![a, "b".repeat(64)]);
+
+    let plain = parse_version_spec("latest:^1\\.5").unwrap();
+    assert_eq!(plain.spec, "latest:^1\\.5");
+    assert!(plain.sha256.is_empty());
+
+    assert!(parse_version_spec("1.6.3 sha256:abc").is_err());
+}
+
+#[test]
+fn test_collect_tree_pins() {
+    use std::fs;
+    use tfenv_rs::product::Product;
+    use tfenv_rs::version::collect_tree_pins;
+    let tmp = tempfile::TempDir::new().unwrap();
+    let root = tmp.path();
+    fs::create_dir_all(root.join("a")).unwrap();
+    fs::create_dir_all(root.join("b")).unwrap();
+    fs::create_dir_all(root.join("ignored")).unwrap();
+    fs::create_dir_all(root.join("b/.terraform/modules/m")).unwrap();
+    fs::write(root.join(".gitignore"), "ignored/\n").unwrap();
+    fs::write(root.join("a/.terraform-version"), "1.6.3\n").unwrap();
+    fs::write(
+        root.join("b/main.tf"),
+        "terraform {\n  required_version = \"~> 1.5.0\"\n}\n",
+    )
+    .unwrap();
+    fs::write(root.join("ignored/.terraform-version"), "9.9.9\n").unwrap();
+    fs::write(
+        root.join("b/.terraform/modules/m/main.tf"),
+        "terraform {\n  required_version = \"9.9.9\"\n}\n",
+    )
+    .unwrap();
+
+    let pins = collect_tree_pins(root, &Product::from_name("terraform").unwrap()).unwrap();
+    let found: Vec<(&str, &str)> = pins
+        .iter()
+        .map(|p| {
+            (
+                p.source.strip_prefix(root).unwrap().to_str().unwrap(),
+                p.spec.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        found,
+        vec![("a/.terraform-version", "1.6.3"), ("b", "latest-allowed")]
+    );
+}
+
+#[test]
+fn test_collect_tree_pins_skips_modules_and_pinned_dirs() {
+    use std::fs;
+    use tfenv_rs::product::Product;
+    use tfenv_rs::version::collect_tree_pins;
+    let tmp = tempfile::TempDir::new().unwrap();
+    let root = tmp.path();
+    let constraint = "terraform {\n  required_version = \"~> 1.5\"\n}\n";
+    let write = |path: &str, body: &str| {
+        let path = root.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, body).unwrap();
+    };
+    // a stack and the local module it calls
+    write(
+        "stacks/app/main.tf",
+        &format!(
+            "{}module \"net\" {{\n  source = \"../../modules/net\"\n}}\n",
+            constraint
+        ),
+    );
+    write("modules/net/main.tf", constraint);
+    // a pinned stack: its constraints and those below it are not run
+    write("pinned/.terraform-version", "1.6.3\n");
+    write("pinned/main.tf", constraint);
+    write("pinned/sub/main.tf", constraint);
+    // latest-allowed resolves against the constraints of each directory below
+    write("floating/.terraform-version", "latest-allowed\n");
+    write("floating/sub/main.tf", constraint);
+
+    let pins = collect_tree_pins(root, &Product::from_name("terraform").unwrap()).unwrap();
+    let found: Vec<(&str, &str)> = pins
+        .iter()
+        .map(|p| {
+            (
+                p.source.strip_prefix(root).unwrap().to_str().unwrap(),
+                p.spec.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            ("floating/.terraform-version", "latest-allowed"),
+            ("floating/sub", "latest-allowed"),
+            ("pinned/.terraform-version", "1.6.3"),
+            ("stacks/app", "latest-allowed"),
+        ]
+    );
+}
+
+#[test]
+fn test_collect_tree_pins_workspace_files() {
+    use std::fs;
+    use tfenv_rs::product::Product;
+    use tfenv_rs::version::collect_tree_pins;
+    let tmp = tempfile::TempDir::new().unwrap();
+    let root = tmp.path();
+    fs::write(root.join(".terraform-version"), "1.6.6\n").unwrap();
+    fs::write(root.join(".terraform-version.prod"), "1.5.7\n").unwrap();
+    // editor and merge leftovers are not workspace pins, and are not read
+    fs::write(root.join(".terraform-version.swp"), b"\xff\xfe\x00").unwrap();
+    fs::write(root.join(".terraform-version.orig"), "0.12.0\n").unwrap();
+    fs::write(root.join(".terraform-version.a b"), "0.13.0\n").unwrap();
+    // an unreadable pin is skipped rather than failing the walk
+    fs::create_dir_all(root.join("broken")).unwrap();
+    fs::write(root.join("broken/.terraform-version.dev"), b"\xff\xfe\x00").unwrap();
+
+    let pins = collect_tree_pins(root, &Product::from_name("terraform").unwrap()).unwrap();
+    let found: Vec<(&str, &str)> = pins
+        .iter()
+        .map(|p| {
+            (
+                p.source.strip_prefix(root).unwrap().to_str().unwrap(),
+                p.spec.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        found,
+        vec![
+            (".terraform-version", "1.6.6"),
+            (".terraform-version.prod", "1.5.7")
+        ]
+    );
+}
+
+#[test]
+fn test_collect_detected_tree_pins() {
+    use std::fs;
+    use tfenv_rs::product::Product;
+    use tfenv_rs::version::{collect_detected_tree_pins, collect_tree_pins};
+    let tmp = tempfile::TempDir::new().unwrap();
+    let root = tmp.path();
+    let required = "terraform {\n  required_version = \">= 1.5\"\n}\n";
+    for dir in ["tf", "tofu", "pinned"] {
+        fs::create_dir_all(root.join(dir)).unwrap();
+    }
+    fs::write(root.join("tf/main.tf"), required).unwrap();
+    fs::write(root.join("tofu/main.tf"), required).unwrap();
+    fs::write(root.join("tofu/providers.tofu"), "").unwrap();
+    fs::write(root.join("pinned/.opentofu-version"), "1.6.2\n").unwrap();
+    let terraform = Product::from_name("terraform").unwrap();
+    let opentofu = Product::from_name("opentofu").unwrap();
+    let sources = |pins: Vec<tfenv_rs::version::TreePin>| {
+        pins.into_iter()
+            .map(|p| p.source.strip_prefix(root).unwrap().display().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // each stack goes to the product detected in it
+    assert_eq!(
+        sources(collect_detected_tree_pins(root, &terraform).unwrap()),
+        ["tf"]
+    );
+    assert_eq!(
+        sources(collect_detected_tree_pins(root, &opentofu).unwrap()),
+        ["pinned/.opentofu-version", "tofu"]
+    );
+    // a product chosen explicitly takes every stack
+    assert_eq!(
+        sources(collect_tree_pins(root, &terraform).unwrap()),
+        ["tf", "tofu"]
+    );
+}
+
+#[test]
+fn test_trace_text_and_json() {
+    use std::path::Path;
+    use tfenv_rs::trace::Trace;
+
+    let mut trace = Trace::default();
+    trace.note("env", "TFENV_TERRAFORM_VERSION is not set");
+    trace.note_path("version-file", Path::new("/w/.terraform-version"), "wins");
+    assert_eq!(
+        trace.to_string(),
+        "env           TFENV_TERRAFORM_VERSION is not set\nversion-file  /w/.terraform-version: wins\n"
+    );
+
+    let json: serde_json::Value =
+        serde_json::from_str(&trace.to_json(&Ok("1.5.7".to_string())).unwrap()).unwrap();
+    assert_eq!(json["version"], "1.5.7");
+    assert_eq!(json["steps"][1]["path"], "/w/.terraform-version");
+    assert!(json["steps"][0].get("path").is_none());
+
+    let json: serde_json::Value =
+        serde_json::from_str(&trace.to_json(&Err(anyhow::anyhow!("offline"))).unwrap()).unwrap();
+    assert_eq!(json["error"], "offline");
+    assert!(json.get("version").is_none());
+}
+
+#[test]
+fn test_chdir_arg() {
+    use tfenv_rs::version::chdir_arg;
+
+    let args = |list: &[&str]| list.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+    assert_eq!(
+        chdir_arg(&args(&["-chdir=stacks/prod", "plan"])),
+        Some("stacks/prod")
+    );
+    assert_eq!(
+        chdir_arg(&args(&["-no-color", "--chdir=x", "plan"])),
+        Some("x")
+    );
+    // only global options before the subcommand count
+    assert_eq!(chdir_arg(&args(&["plan", "-chdir=x"])), None);
+    assert_eq!(chdir_arg(&args(&["version"])), None);
+}
+
+#[test]
+fn test_tool_versions() {
+    use tfenv_rs::product::Product;
+    use tfenv_rs::tool_versions::{parse_tool_versions, write_tool_versions};
+
+    let terraform = Product::from_name("terraform").unwrap();
+    let opentofu = Product::from_name("opentofu").unwrap();
+    let body = "# tools\nnodejs 20.1.0\nterraform 1.5.7 1.5.6 # fallback\ntofu system\n";
+    assert_eq!(
+        parse_tool_versions(body, &terraform),
+        Some("1.5.7".to_string())
+    );
+    // listed under its binary name, but pinned to a version tfenv cannot install
+    assert_eq!(parse_tool_versions(body, &opentofu), None);
+    assert_eq!(parse_tool_versions("nodejs 20.1.0\n", &terraform), None);
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(".tool-versions");
+    std::fs::write(&path, body).unwrap();
+    write_tool_versions(&path, &terraform, "1.6.0").unwrap();
+    write_tool_versions(&path, &opentofu, "1.6.2").unwrap();
+    assert_eq!(
+        std::fs::read_to_string(&path).unwrap(),
+        "# tools\nnodejs 20.1.0\nterraform 1.6.0\nopentofu 1.6.2\n"
+    );
+}
+
+#[test]
+fn test_version_file_workspace_sections() {
+    use tfenv_rs::version::VersionFile;
+
+    let file = VersionFile::parse(
+        "# pins\n1.6.6\n[workspace.prod] 1.5.7\n[workspace.staging]\n\n~> 1.6.0 # next\n",
+    )
+    .unwrap();
+    let spec_for = |ws: Option<&str>| file.spec_for(ws).map(|s| s.spec.clone());
+    assert_eq!(spec_for(None), Some("1.6.6".to_string()));
+    assert_eq!(spec_for(Some("prod")), Some("1.5.7".to_string()));
+    assert_eq!(spec_for(Some("staging")), Some("~> 1.6.0".to_string()));
+    // other workspaces fall back to the default
+    assert_eq!(spec_for(Some("dev")), Some("1.6.6".to_string()));
+
+    let sections_only = VersionFile::parse("[workspace.prod]\n1.5.7\n").unwrap();
+    assert!(sections_only.spec_for(Some("dev")).is_none());
+    assert!(VersionFile::parse("[prod] 1.5.7\n").is_err());
+}
+
+#[test]
+fn test_state_version() {
+    use tfenv_rs::state::state_version;
+
+    let dir = tempfile::tempdir().unwrap();
+    assert!(state_version(dir.path()).unwrap().is_none());
+
+    let local = dir.path().join("terraform.tfstate");
+    std::fs::write(&local, r#"{"version": 4, "terraform_version": "1.5.7"}"#).unwrap();
+    let state = state_version(dir.path()).unwrap().unwrap();
+    assert_eq!(state.version.to_string(), "1.5.7");
+    assert_eq!(state.path, local);
+
+    // the cached backend state counts too, and the newest version wins
+    let cached = dir.path().join(".terraform").join("terraform.tfstate");
+    std::fs::create_dir_all(cached.parent().unwrap()).unwrap();
+    std::fs::write(&cached, r#"{"version": 3, "terraform_version": "1.6.2"}"#).unwrap();
+    let state = state_version(dir.path()).unwrap().unwrap();
+    assert_eq!(state.version.to_string(), "1.6.2");
+    assert_eq!(state.path, cached);
+
+    // the `state` keyword, for the products that use Terraform state
+    let resolve = |product: &str| {
+        tfenv_rs::version::resolve_install_spec(
+            "state",
+            dir.path(),
+            dir.path(),
+            dir.path(),
+            &tfenv_rs::product::Product::from_name(product).unwrap(),
+        )
+    };
+    assert_eq!(resolve("terraform").unwrap(), "1.6.2");
+    assert_eq!(resolve("opentofu").unwrap(), "1.6.2");
+    let err = resolve("packer").unwrap_err();
+    assert!(err
+        .to_string()
+        .contains("packer does not use Terraform state"));
+
+    std::fs::write(&local, r#"{"terraform_version": "one"}"#).unwrap();
+    assert!(state_version(dir.path()).is_err());
+}
+
+#[test]
+fn test_workspace_version_file_wins() {
+    use std::fs;
+    use tfenv_rs::product::Product;
+    use tfenv_rs::version::{is_workspace_name, resolve_version_in};
+
+    assert!(is_workspace_name("prod"));
+    assert!(is_workspace_name("eu-west-1_v2.1"));
+    assert!(!is_workspace_name(""));
+    assert!(!is_workspace_name("../prod"));
+    assert!(!is_workspace_name("a/b"));
+    assert!(!is_workspace_name(".hidden"));
+
+    let tmp = tempfile::TempDir::new().unwrap();
+    let dir = tmp.path();
+    let config = dir.join("config");
+    let terraform = Product::from_name("terraform").unwrap();
+    fs::create_dir_all(dir.join(".terraform")).unwrap();
+    fs::write(dir.join(".terraform-version"), "1.6.6\n").unwrap();
+    fs::write(dir.join(".terraform-version.prod"), "1.5.7\n").unwrap();
+    let resolve = || {
+        resolve_version_in(dir, &config, &terraform, dir)
+            .unwrap()
+            .version
+    };
+
+    assert_eq!(resolve(), "1.6.6");
+    fs::write(dir.join(".terraform/environment"), "prod").unwrap();
+    assert_eq!(resolve(), "1.5.7");
+    // a name that could escape the directory selects no workspace
+    fs::write(dir.join(".terraform/environment"), "../prod").unwrap();
+    assert_eq!(resolve(), "1.6.6");
+}
+
+#[test]
+fn test_terragrunt_version_constraint() {
+    use std::fs;
+    use tfenv_rs::product::Product;
+    use tfenv_rs::version::resolve_version_in;
+
+    let tmp = tempfile::TempDir::new().unwrap();
+    let root = tmp.path();
+    let config = root.join("config");
+    for v in ["0.48.0", "0.54.2", "0.55.1"] {
+        fs::create_dir_all(config.join("terragrunt/versions").join(v)).unwrap();
+    }
+    let live = root.join("live");
+    let app = live.join("app");
+    fs::create_dir_all(&app).unwrap();
+    fs::write(
+        live.join("terragrunt.hcl"),
+        "terragrunt_version_constraint = \">= 0.50, < 0.55\"\n",
+    )
+    .unwrap();
+    let terragrunt = Product::from_name("terragrunt").unwrap();
+    let resolve = || {
+        resolve_version_in(root, &config, &terragrunt, &app)
+            .unwrap()
+            .version
+    };
+
+    // the constraint acts as latest-allowed
+    assert_eq!(resolve(), "0.54.2");
+    // a version file further up loses to the nearer constraint
+    fs::write(root.join(".terragrunt-version"), "0.55.1\n").unwrap();
+    assert_eq!(resolve(), "0.54.2");
+    // one in the constraint's directory wins
+    fs::write(live.join(".terragrunt-version"), "0.48.0\n").unwrap();
+    assert_eq!(resolve(), "0.48.0");
+    // and so does a nearer one
+    fs::write(app.join(".terragrunt-version"), "0.55.1\n").unwrap();
+    assert_eq!(resolve(), "0.55.1");
+}